@@ -1,12 +1,14 @@
 use crate::commands::BottomPaneCommand;
+use crate::length_editor::LengthEditor;
+use crate::pitch_editor::PitchEditor;
+use crate::waveform::Waveform;
 
-use eframe::egui::{
-    Align, Align2, FontId, Layout, NumExt, Response, SelectableLabel, TextStyle, Ui, Vec2, Widget,
-};
-use std::ops::RangeInclusive;
+use eframe::egui::{Align2, FontId, NumExt, SelectableLabel, TextStyle, Ui, Vec2};
 use voice_vox_api::api_schema::AccentPhraseInProject;
 
 /// アクセント位置とアクセント句の変化で新しくリクエストを送る必要がある.
+///
+/// see [BottomPaneCommand::affected_accent_phrases].
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub enum Displaying {
     Accent,
@@ -14,11 +16,20 @@ pub enum Displaying {
     Length,
 }
 
+/// * `playing` : shows stop button instead of play button.
+/// * `waveform` : synthesized audio of current line, drawn in intonation and length view.
+/// * `playhead` : seconds since playback started.
+#[allow(clippy::too_many_arguments)]
 pub fn create_bottom_pane(
     current_displaying: &mut Displaying,
+    pitch_editor: &mut PitchEditor,
+    length_editor: &mut LengthEditor,
     should_play: &mut Option<bool>,
     ui: &mut Ui,
     edit_targets: &[AccentPhraseInProject],
+    playing: bool,
+    waveform: Option<&Waveform>,
+    playhead: Option<f32>,
 ) -> Option<BottomPaneCommand> {
     let mut rt = None;
     ui.horizontal(|ui| {
@@ -71,7 +82,7 @@ pub fn create_bottom_pane(
                 let box_rect = response.rect.shrink(radius * (3.0 / 4.0));
                 painter.circle_filled(center, radius, Color32::DARK_GREEN);
 
-                if false {
+                if playing {
                     let rounding = Rounding::none();
                     painter.rect(box_rect, rounding, Color32::BLACK, Stroke::none());
                     if response.clicked() {
@@ -113,6 +124,9 @@ pub fn create_bottom_pane(
                 let mut space = ui.spacing().item_spacing;
                 space.y = ui.available_height() / 1.2;
                 space.x *= 6.0;
+                if let (Some(waveform), Intonation | Length) = (waveform, *current_displaying) {
+                    waveform.ui(ui, playhead);
+                }
                 match current_displaying {
                     Displaying::Accent => {
                         let accent_phrase_len = edit_targets.len();
@@ -146,6 +160,18 @@ pub fn create_bottom_pane(
                                                 prev_accent: edit_target.accent as usize,
                                             });
                                         }
+                                        let interrogative =
+                                            edit_target.isInterrogative.unwrap_or(false);
+                                        if ui
+                                            .selectable_label(interrogative, "？")
+                                            .on_hover_text("疑問文")
+                                            .clicked()
+                                        {
+                                            rt = Some(BottomPaneCommand::Interrogative {
+                                                accent_phrase: ap,
+                                                is_interrogative: Some(!interrogative),
+                                            });
+                                        }
                                         let h = ui.available_height();
                                         let w = res.rect.width();
                                         let (r, painter) = ui.allocate_painter(
@@ -222,6 +248,7 @@ pub fn create_bottom_pane(
                                             rt = Some(BottomPaneCommand::Concat {
                                                 accent_phrase: ap,
                                                 length: mora_len,
+                                                removed: None,
                                             });
                                         }
                                     }
@@ -230,123 +257,17 @@ pub fn create_bottom_pane(
                         }
                     }
                     Displaying::Intonation => {
-                        let accent_phrase_len = edit_targets.len();
                         if !edit_targets.is_empty() {
-                            ui.horizontal(|ui| {
-                                for (ap, edit_target) in edit_targets.iter().enumerate() {
-                                    let mora_len = edit_target.moras.len();
-                                    for (index, mora) in edit_target.moras.iter().enumerate() {
-                                        let mut pitch = mora.pitch;
-                                        let slider =
-                                            eframe::egui::Slider::new(&mut pitch, 3.0..=6.5)
-                                                .vertical()
-                                                .text(&mora.text)
-                                                .show_value(false);
-                                        let res = ui.add(slider);
-
-                                        if (res.clicked() | res.drag_released())
-                                            & ((pitch - mora.pitch).abs() > f32::EPSILON)
-                                        {
-                                            //emit signal.
-                                            rt = Some(BottomPaneCommand::Pitch {
-                                                accent_phrase: ap,
-                                                mora: index,
-                                                pitch_diff: pitch - mora.pitch,
-                                            });
-                                        }
-                                    }
-                                    if ap < accent_phrase_len - 1 {
-                                        let button = eframe::egui::Button::new("");
-                                        if ui.add_sized(space, button).clicked() {
-                                            rt = Some(BottomPaneCommand::Concat {
-                                                accent_phrase: ap,
-                                                length: mora_len,
-                                            });
-                                        }
-                                    }
-                                }
-                            });
+                            if let Some(cmd) = pitch_editor.ui(ui, edit_targets) {
+                                rt = Some(cmd);
+                            }
                         }
                     }
                     Displaying::Length => {
-                        let accent_phrase_len = edit_targets.len();
                         if !edit_targets.is_empty() {
-                            ui.horizontal(|ui| {
-                                for (ap, edit_target) in edit_targets.iter().enumerate() {
-                                    let mora_len = edit_target.moras.len();
-                                    for (index, mora) in edit_target.moras.iter().enumerate() {
-                                        if let Some(prev_consonant) = mora.consonantLength {
-                                            let mut consonant = prev_consonant;
-                                            let mut vowel = mora.vowelLength;
-                                            let slider = TwoNotchSlider {
-                                                a: &mut consonant,
-                                                b: &mut vowel,
-                                                range: 0.0..=0.30,
-                                                text: mora.text.clone(),
-                                            };
-                                            let res = ui.add(slider);
-
-                                            let vowel_diff = if (res.clicked()
-                                                | res.drag_released())
-                                                & ((vowel - mora.vowelLength).abs() > f32::EPSILON)
-                                            {
-                                                log::debug!("vowel {}", vowel);
-                                                Some(vowel - mora.vowelLength)
-                                            } else {
-                                                None
-                                            };
-                                            let consonant_diff = if (res.clicked()
-                                                | res.drag_released())
-                                                & ((consonant - prev_consonant).abs()
-                                                    > f32::EPSILON)
-                                            {
-                                                log::debug!("consonant {}", consonant);
-                                                Some(consonant - prev_consonant)
-                                            } else {
-                                                None
-                                            };
-                                            if res.clicked() | res.drag_released() {
-                                                //emit signal.
-                                                rt = Some(BottomPaneCommand::VowelAndConsonant {
-                                                    accent_phrase: ap,
-                                                    mora: index,
-                                                    vowel_diff,
-                                                    consonant_diff,
-                                                });
-                                            }
-                                        } else {
-                                            let mut vowel = mora.vowelLength;
-                                            let slider =
-                                                eframe::egui::Slider::new(&mut vowel, 0.0..=0.30)
-                                                    .vertical()
-                                                    .text(&mora.text)
-                                                    .show_value(false);
-                                            let res = ui.add(slider);
-
-                                            if (res.clicked() | res.drag_released())
-                                                & ((vowel - mora.vowelLength).abs() > f32::EPSILON)
-                                            {
-                                                //emit signal.
-                                                rt = Some(BottomPaneCommand::VowelAndConsonant {
-                                                    accent_phrase: ap,
-                                                    mora: index,
-                                                    vowel_diff: Some(vowel - mora.vowelLength),
-                                                    consonant_diff: None,
-                                                });
-                                            }
-                                        };
-                                    }
-                                    if ap < accent_phrase_len - 1 {
-                                        let button = eframe::egui::Button::new("");
-                                        if ui.add_sized(space, button).clicked() {
-                                            rt = Some(BottomPaneCommand::Concat {
-                                                accent_phrase: ap,
-                                                length: mora_len,
-                                            });
-                                        }
-                                    }
-                                }
-                            });
+                            if let Some(cmd) = length_editor.ui(ui, edit_targets) {
+                                rt = Some(cmd);
+                            }
                         }
                     }
                 }
@@ -355,138 +276,3 @@ pub fn create_bottom_pane(
     });
     rt
 }
-
-pub struct TwoNotchSlider<'a> {
-    pub a: &'a mut f32,
-    pub b: &'a mut f32,
-    pub range: RangeInclusive<f32>,
-    pub text: String,
-}
-
-impl<'a> TwoNotchSlider<'a> {
-    fn slider_ui(self, ui: &mut Ui) -> Response {
-        use eframe::egui;
-        use egui::epaint::{pos2, vec2, Color32, Rect, Stroke};
-        use egui::Sense;
-        let width_of_rail = 8.0;
-
-        let height = ui.available_height();
-
-        let (_id, rect) = ui.allocate_space(vec2(width_of_rail * 2.0, ui.available_height()));
-        let origin = rect.min;
-
-        let res_left = ui.allocate_rect(
-            Rect::from_min_max(origin, origin + vec2(width_of_rail, height)),
-            Sense::click_and_drag(),
-        );
-
-        let painter = ui.painter_at(rect);
-        painter.vline(
-            origin.x + width_of_rail,
-            origin.y..=origin.y + height,
-            Stroke::new(width_of_rail, Color32::LIGHT_GRAY),
-        );
-
-        let bottom = origin.y + height;
-
-        let width_of_range = *self.range.end() - *self.range.start();
-
-        let a = if let Some(pos) = res_left.interact_pointer_pos() {
-            let cursor_pos = pos.y;
-            let x = bottom - cursor_pos;
-            let scale = x / height;
-            egui::lerp(self.range.clone(), scale)
-        } else {
-            *self.a
-        };
-
-        let a_diff_from_start = a - *self.range.start();
-        let a_points_from_bottom = (a_diff_from_start / width_of_range) * height;
-        let center = pos2(origin.x + width_of_rail, bottom - a_points_from_bottom);
-
-        let thickness = ui
-            .text_style_height(&TextStyle::Body)
-            .at_least(ui.spacing().interact_size.y);
-        let radius = thickness / 2.5;
-
-        let (left, right) = HALF_CIRCLE.get_or_init(|| {
-            use eframe::epaint::Shape;
-            // - pi/2 -> pi/2
-            let offset = -std::f32::consts::FRAC_PI_2;
-            let unit_angle = std::f32::consts::TAU / 24.0;
-
-            let right = (0..=12).into_iter().map(|x| {
-                let phase = x as f32 * unit_angle + offset;
-                let (sin, cos) = phase.sin_cos();
-                pos2(cos * radius, sin * radius)
-            });
-            let left = (12..=24).into_iter().map(|x| {
-                let phase = x as f32 * unit_angle + offset;
-                let (sin, cos) = phase.sin_cos();
-                pos2(cos * radius, sin * radius)
-            });
-            let right = Shape::convex_polygon(
-                right.collect(),
-                Color32::LIGHT_GRAY,
-                Stroke::new(1.0, Color32::BLACK),
-            );
-            let left = Shape::convex_polygon(
-                left.collect(),
-                Color32::LIGHT_GRAY,
-                Stroke::new(1.0, Color32::BLACK),
-            );
-            (left, right)
-        });
-
-        let mut left = left.clone();
-        left.translate(center.to_vec2());
-        if res_left.hovered() {
-            painter.circle_filled(center, radius * 1.4, Color32::LIGHT_GREEN);
-        }
-        painter.add(left);
-
-        let mut right = right.clone();
-
-        let right_origin = origin + vec2(width_of_rail, 0.0);
-        let res_right = ui.allocate_rect(
-            Rect::from_min_max(right_origin, right_origin + vec2(width_of_rail, height)),
-            Sense::click_and_drag(),
-        );
-
-        let b = if let Some(pos) = res_right.interact_pointer_pos() {
-            let cursor_pos = pos.y;
-            let x = bottom - cursor_pos;
-            let scale = x / height;
-            egui::lerp(self.range.clone(), scale)
-        } else {
-            *self.b
-        };
-
-        let b_diff_from_start = b - *self.range.start();
-        let b_points_from_bottom = (b_diff_from_start / width_of_range) * height;
-        let center = pos2(origin.x + width_of_rail, bottom - b_points_from_bottom);
-        right.translate(center.to_vec2());
-        if res_right.hovered() {
-            painter.circle_filled(center, radius * 1.4, Color32::LIGHT_GREEN);
-        }
-        painter.add(right);
-        *self.a = f32::clamp(a, *self.range.start(), *self.range.end());
-        *self.b = f32::clamp(b, *self.range.start(), *self.range.end());
-        res_left.union(res_right)
-    }
-}
-
-static HALF_CIRCLE: once_cell::sync::OnceCell<(eframe::epaint::Shape, eframe::epaint::Shape)> =
-    once_cell::sync::OnceCell::new();
-
-impl<'a> Widget for TwoNotchSlider<'a> {
-    fn ui(self, ui: &mut Ui) -> Response {
-        ui.with_layout(Layout::bottom_up(Align::Min), |ui| {
-            ui.label(&self.text);
-            {
-                self.slider_ui(ui)
-            }
-        })
-        .inner
-    }
-}
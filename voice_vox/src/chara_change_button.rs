@@ -1,19 +1,23 @@
 use crate::api;
 use crate::api::Api;
-use crate::history::Command;
+use crate::history::{Command, CommandRecord, MERGE_WINDOW};
 use crate::project::VoiceVoxProject;
 use eframe::egui;
 use eframe::egui::Ui;
 use egui::Context;
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 
 pub(crate) static ICON_AND_PORTRAIT_STORE: once_cell::race::OnceBox<
     HashMap<(String, String), egui_extras::RetainedImage>,
 > = once_cell::race::OnceBox::new();
 
+/// character name and its styles as name and id.
+type StyleStructure = Vec<(String, Vec<(String, i32)>)>;
+
 /// used for construct chara changing menu.
-static STYLE_STRUCTURE: once_cell::race::OnceBox<Vec<(String, Vec<(String, i32)>)>> =
-    once_cell::race::OnceBox::new();
+static STYLE_STRUCTURE: once_cell::race::OnceBox<StyleStructure> = once_cell::race::OnceBox::new();
 
 /// used for generic usage.
 pub static STYLE_ID_AND_CHARA_TABLE: once_cell::race::OnceBox<BTreeMap<i32, (String, String)>> =
@@ -49,7 +53,7 @@ pub(crate) async fn init_icon_store() -> Option<()> {
                 .ok()?,
             );
             let mut style_infos = speaker_info.style_infos;
-            style_infos.sort_by(|a, b| a.id.cmp(&b.id));
+            style_infos.sort_by_key(|style| style.id);
             let mut style_names = Vec::new();
             for (style, info) in speaker.styles.iter().zip(style_infos.iter()) {
                 let sty_name = style.name.clone();
@@ -97,7 +101,7 @@ impl CharaChangeButton {
             egui::vec2(32.0, 32.0),
             |ui| {
                 for (character, styles) in style_structure {
-                    if let Some((default_style, speaker)) = styles.get(0) {
+                    if let Some((default_style, speaker)) = styles.first() {
                         if let Some(default_icon) =
                             image.get(&(character.clone(), default_style.clone()))
                         {
@@ -164,6 +168,7 @@ impl CharaChangeButton {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct CharaChangeCommand {
     pub prev_chara: i32,
     pub new_chara: i32,
@@ -181,6 +186,27 @@ impl Command for CharaChangeCommand {
             audio_item.styleId = self.prev_chara;
         }
     }
+    fn to_record(&self) -> CommandRecord {
+        CommandRecord::CharaChange(self.clone())
+    }
+
+    fn merge(
+        &mut self,
+        own_uuid: &str,
+        next: &CommandRecord,
+        next_uuid: &str,
+        _project: &VoiceVoxProject,
+        elapsed: Duration,
+    ) -> bool {
+        if let CommandRecord::CharaChange(next) = next {
+            if own_uuid == next_uuid && elapsed < MERGE_WINDOW {
+                self.new_chara = next.new_chara;
+                return true;
+            }
+        }
+        false
+    }
+
     fn op_name(&self) -> &str {
         "キャラクター変更"
     }
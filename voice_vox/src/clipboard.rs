@@ -0,0 +1,132 @@
+use crate::project::AudioItem;
+use serde::{Deserialize, Serialize};
+
+/// tag of clipboard text holding lines copied in this app.
+const FORMAT: &str = "voice_vox_rust/lines";
+
+/// contents of clipboard resolved for pasting.
+#[derive(PartialEq)]
+pub enum Pasted {
+    /// lines copied in this app with style and query.
+    Lines(Vec<AudioItem>),
+    /// text from other application. each non-empty line becomes new line.
+    Text(Vec<String>),
+}
+
+/// lines written into system clipboard as json text.
+#[derive(Serialize, Deserialize)]
+struct Payload {
+    format: String,
+    items: Vec<AudioItem>,
+}
+
+/// copies lines into system clipboard as tagged json.
+///
+/// lines pasted from the json keep style and tuning, even across app instances.
+#[derive(Default)]
+pub struct LineClipboard {
+    /// kept alive because dropping it clears clipboard on some platforms.
+    system: Option<arboard::Clipboard>,
+    /// last copied lines. used when system clipboard is unavailable.
+    copied: Option<Vec<AudioItem>>,
+}
+
+fn to_payload(items: &[AudioItem]) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&Payload {
+        format: FORMAT.to_owned(),
+        items: items.to_vec(),
+    })
+}
+
+/// lines in `text` if it is written by [to_payload].
+fn from_payload(text: &str) -> Option<Vec<AudioItem>> {
+    match serde_json::from_str::<Payload>(text) {
+        Ok(payload) if payload.format == FORMAT => Some(payload.items),
+        _ => None,
+    }
+}
+
+/// `text` is current contents of system clipboard, `None` if it could not be read.
+fn resolve(copied: Option<&Vec<AudioItem>>, text: Option<String>) -> Option<Pasted> {
+    match (copied, text) {
+        (_, Some(text)) => {
+            if let Some(items) = from_payload(&text) {
+                return Some(Pasted::Lines(items));
+            }
+            let lines: Vec<String> = text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_owned)
+                .collect();
+            if lines.is_empty() {
+                None
+            } else {
+                Some(Pasted::Text(lines))
+            }
+        }
+        (Some(items), None) => Some(Pasted::Lines(items.clone())),
+        (None, None) => None,
+    }
+}
+
+impl LineClipboard {
+    fn system(&mut self) -> Option<&mut arboard::Clipboard> {
+        if self.system.is_none() {
+            self.system = arboard::Clipboard::new()
+                .map_err(|e| log::error!("system clipboard is unavailable {}", e))
+                .ok();
+        }
+        self.system.as_mut()
+    }
+
+    pub fn copy(&mut self, items: Vec<AudioItem>) {
+        match to_payload(&items) {
+            Ok(text) => {
+                if let Some(system) = self.system() {
+                    if let Err(e) = system.set_text(text) {
+                        log::error!("failed to write clipboard {}", e);
+                    }
+                }
+            }
+            Err(e) => log::error!("failed to serialize copied lines {}", e),
+        }
+        self.copied = Some(items);
+    }
+
+    pub fn paste(&mut self) -> Option<Pasted> {
+        let text = self.system().and_then(|system| system.get_text().ok());
+        resolve(self.copied.as_ref(), text)
+    }
+
+    /// copied line whose text is `text`. its query can be pasted as tuning.
+    pub fn tuning_for(&self, text: &str) -> Option<&AudioItem> {
+        self.copied
+            .as_ref()?
+            .iter()
+            .find(|item| !text.is_empty() && item.text == text && item.query.is_some())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{resolve, to_payload, Pasted};
+    use crate::project::test::sample_project;
+
+    #[test]
+    fn paste_own_lines_or_foreign_text() {
+        let items = vec![sample_project().audioItems["line"].clone()];
+        let payload = to_payload(&items).unwrap();
+        // lines come back from json even if copied by other instance.
+        assert!(resolve(None, Some(payload.clone())) == Some(Pasted::Lines(items.clone())));
+        assert!(
+            resolve(Some(&items), Some("一行目\n\n 二行目 ".to_owned()))
+                == Some(Pasted::Text(vec!["一行目".to_owned(), "二行目".to_owned()]))
+        );
+        // json of other format is pasted as text.
+        let foreign = payload.replace("voice_vox_rust/lines", "other");
+        assert!(resolve(None, Some(foreign.clone())) == Some(Pasted::Text(vec![foreign])));
+        assert!(resolve(Some(&items), None) == Some(Pasted::Lines(items)));
+        assert!(resolve(None, Some("\n".to_owned())).is_none());
+    }
+}
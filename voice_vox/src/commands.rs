@@ -1,15 +1,32 @@
-use crate::history::Command;
+use crate::history::{Command, CommandRecord, MERGE_WINDOW};
 use crate::project::VoiceVoxProject;
 use crate::{api_schema, project};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use voice_vox_api::kana::{to_kana, KanaPhrase};
 
+#[derive(Serialize, Deserialize, Clone)]
 pub enum AudioQueryCommands {
     Remove(usize, Option<project::AudioItem>),
-    Insert(project::AudioItem),
+    /// insert line at index of `audioKeys`.
+    Insert(usize, project::AudioItem),
+    /// move line to index `to`, which is counted after the line is taken out.
+    Move {
+        from: usize,
+        to: usize,
+    },
     UpdateAccentPhrases {
         new_text: String,
         prev_text: String,
         accent_phrases: Vec<api_schema::AccentPhraseInProject>,
     },
+    /// replace accent phrases from `start` with pitch and length recalculated by engine.
+    UpdateMoraData {
+        start: usize,
+        accent_phrases: Vec<api_schema::AccentPhraseInProject>,
+    },
+    /// replace whole query with pasted tuning.
+    ReplaceQuery(Option<api_schema::AudioQueryInProject>),
 }
 
 impl Command for AudioQueryCommands {
@@ -26,12 +43,19 @@ impl Command for AudioQueryCommands {
                     save.replace(value);
                 }
             }
-            AudioQueryCommands::Insert(value) => {
+            AudioQueryCommands::Insert(index, value) => {
                 if !project.audioKeys.contains(&uuid.to_owned()) {
-                    project.audioKeys.push(uuid.to_owned());
+                    let index = (*index).min(project.audioKeys.len());
+                    project.audioKeys.insert(index, uuid.to_owned());
                 }
                 project.audioItems.insert(uuid.to_owned(), value.clone());
             }
+            AudioQueryCommands::Move { from, to } => {
+                if let Some(index) = project.audioKeys.iter().position(|x| x == uuid) {
+                    *from = index;
+                    move_line(project, index, *to);
+                }
+            }
             AudioQueryCommands::UpdateAccentPhrases {
                 new_text,
                 prev_text,
@@ -46,6 +70,15 @@ impl Command for AudioQueryCommands {
                     }
                 }
             }
+            AudioQueryCommands::UpdateMoraData {
+                start,
+                accent_phrases,
+            } => swap_accent_phrases(project, uuid, *start, accent_phrases),
+            AudioQueryCommands::ReplaceQuery(query) => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    std::mem::swap(&mut ai.query, query);
+                }
+            }
         }
     }
 
@@ -59,12 +92,17 @@ impl Command for AudioQueryCommands {
                 }
                 *save = None;
             }
-            AudioQueryCommands::Insert(_) => {
-                if project.audioKeys.contains(&uuid.to_owned()) {
-                    project.audioKeys.pop();
+            AudioQueryCommands::Insert(..) => {
+                if let Some(index) = project.audioKeys.iter().position(|x| x == uuid) {
+                    project.audioKeys.remove(index);
                     project.audioItems.remove(uuid);
                 }
             }
+            AudioQueryCommands::Move { from, .. } => {
+                if let Some(index) = project.audioKeys.iter().position(|x| x == uuid) {
+                    move_line(project, index, *from);
+                }
+            }
             AudioQueryCommands::UpdateAccentPhrases {
                 new_text,
                 prev_text,
@@ -79,18 +117,183 @@ impl Command for AudioQueryCommands {
                     }
                 }
             }
+            AudioQueryCommands::UpdateMoraData {
+                start,
+                accent_phrases,
+            } => swap_accent_phrases(project, uuid, *start, accent_phrases),
+            AudioQueryCommands::ReplaceQuery(query) => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    std::mem::swap(&mut ai.query, query);
+                }
+            }
+        }
+    }
+
+    fn to_record(&self) -> CommandRecord {
+        CommandRecord::AudioQuery(self.clone())
+    }
+
+    fn merge(
+        &mut self,
+        own_uuid: &str,
+        next: &CommandRecord,
+        next_uuid: &str,
+        project: &VoiceVoxProject,
+        elapsed: Duration,
+    ) -> bool {
+        if own_uuid != next_uuid {
+            return false;
+        }
+        match (self, next) {
+            // first query of inserted line.
+            (
+                AudioQueryCommands::Insert(_, value),
+                CommandRecord::AudioQuery(AudioQueryCommands::UpdateAccentPhrases { .. }),
+            ) => {
+                if let Some(ai) = project.audioItems.get(own_uuid) {
+                    *value = ai.clone();
+                    true
+                } else {
+                    false
+                }
+            }
+            (
+                AudioQueryCommands::UpdateAccentPhrases { new_text, .. },
+                CommandRecord::AudioQuery(AudioQueryCommands::UpdateAccentPhrases {
+                    new_text: next_text,
+                    ..
+                }),
+            ) if elapsed < MERGE_WINDOW => {
+                *new_text = next_text.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn fits(&self, project: &VoiceVoxProject, uuid: &str, undo: bool) -> bool {
+        let exists = project.audioItems.contains_key(uuid);
+        match self {
+            AudioQueryCommands::Remove(pos, _) if undo => {
+                !exists && *pos <= project.audioKeys.len()
+            }
+            AudioQueryCommands::Insert(..) if !undo => true,
+            _ => exists,
         }
     }
 
     fn op_name(&self) -> &str {
         match self {
             AudioQueryCommands::Remove(_, _) => "行削除",
-            AudioQueryCommands::Insert(_) => "行挿入",
+            AudioQueryCommands::Insert(..) => "行挿入",
+            AudioQueryCommands::Move { .. } => "行移動",
             AudioQueryCommands::UpdateAccentPhrases { .. } => "テキスト/波形変更",
+            AudioQueryCommands::UpdateMoraData { .. } => "音高/長さ再計算",
+            AudioQueryCommands::ReplaceQuery(_) => "調整の貼り付け",
+        }
+    }
+}
+
+/// take accent phrases of `next` while keeping tuned phrases of `prev` whose moras are unchanged.
+///
+/// phrases are matched by longest common subsequence of their mora text.
+pub fn carry_over_accent_phrases(
+    prev: &[api_schema::AccentPhraseInProject],
+    next: &[api_schema::AccentPhrase],
+) -> Vec<api_schema::AccentPhraseInProject> {
+    carry_over_by(prev, next, |ap| {
+        ap.moras.iter().map(|m| m.text.as_str()).collect::<String>()
+    })
+}
+
+/// same as [carry_over_accent_phrases] but phrases whose accent or devoicing changed are taken from `next`.
+///
+/// used when reading is edited directly.
+pub fn carry_over_kana_phrases(
+    prev: &[api_schema::AccentPhraseInProject],
+    next: &[api_schema::AccentPhrase],
+) -> Vec<api_schema::AccentPhraseInProject> {
+    carry_over_by(prev, next, |ap| {
+        let mut phrase = KanaPhrase::from(ap);
+        // punctuation is handled separately.
+        phrase.pause = false;
+        to_kana(&[phrase])
+    })
+}
+
+fn carry_over_by<K: PartialEq>(
+    prev: &[api_schema::AccentPhraseInProject],
+    next: &[api_schema::AccentPhrase],
+    key: impl Fn(&api_schema::AccentPhraseInProject) -> K,
+) -> Vec<api_schema::AccentPhraseInProject> {
+    let mut next: Vec<api_schema::AccentPhraseInProject> =
+        next.iter().map(|ap| ap.clone().into()).collect();
+    let prev_key: Vec<K> = prev.iter().map(&key).collect();
+    let next_key: Vec<K> = next.iter().map(&key).collect();
+    // lcs[i][j] : length of common subsequence of prev[i..] and next[j..].
+    let mut lcs = vec![vec![0usize; next.len() + 1]; prev.len() + 1];
+    for i in (0..prev.len()).rev() {
+        for j in (0..next.len()).rev() {
+            lcs[i][j] = if prev_key[i] == next_key[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < prev.len() && j < next.len() {
+        if prev_key[i] == next_key[j] {
+            // punctuation may be added or removed around unchanged phrase.
+            let pause_mora = match (&prev[i].pause_mora, next[j].pause_mora.take()) {
+                (Some(prev_pause), Some(_)) => Some(prev_pause.clone()),
+                (_, next_pause) => next_pause,
+            };
+            next[j] = api_schema::AccentPhraseInProject {
+                pause_mora,
+                isInterrogative: next[j].isInterrogative,
+                ..prev[i].clone()
+            };
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
         }
     }
+    next
 }
 
+fn move_line(project: &mut VoiceVoxProject, from: usize, to: usize) {
+    let line = project.audioKeys.remove(from);
+    let to = to.min(project.audioKeys.len());
+    project.audioKeys.insert(to, line);
+}
+
+/// swap accent phrases of line from `start` with `accent_phrases`.
+fn swap_accent_phrases(
+    project: &mut VoiceVoxProject,
+    uuid: &str,
+    start: usize,
+    accent_phrases: &mut [api_schema::AccentPhraseInProject],
+) {
+    if let Some(aq) = project
+        .audioItems
+        .get_mut(uuid)
+        .and_then(|ai| ai.query.as_mut())
+    {
+        if let Some(target) = aq
+            .accentPhrases
+            .get_mut(start..start + accent_phrases.len())
+        {
+            target.swap_with_slice(accent_phrases);
+            log::debug!("swapped {} accent_phrases from {}", uuid, start);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub enum BottomPaneCommand {
     ///
     /// [[ニ],[ホ],[ン]]*[[シ],[マ],[グ],[ニ]]
@@ -99,18 +302,30 @@ pub enum BottomPaneCommand {
     ///  Concat{
     ///     accent_phrase:0,
     ///     length:3,
+    ///     removed:None,
     ///    }
     /// ```
     ///
-    Concat { accent_phrase: usize, length: usize },
+    /// `removed` holds right accent phrase for undo.
+    Concat {
+        accent_phrase: usize,
+        length: usize,
+        removed: Option<api_schema::AccentPhraseInProject>,
+    },
     ///
     ///
     ///  [ [ニ] [ホ] * [ン] ],[[シ] [マ] [グ] [ニ]]
     ///
     /// ->```
-    /// Split{accent_phrase:0,mora:2}
+    /// Split{accent_phrase:0,mora:2,prev_accent:0}
     /// ```
-    Split { accent_phrase: usize, mora: usize },
+    ///
+    /// `prev_accent` holds accent of left accent phrase for undo.
+    Split {
+        accent_phrase: usize,
+        mora: usize,
+        prev_accent: i32,
+    },
 
     AccentPhrase {
         accent_phrase: usize,
@@ -128,6 +343,126 @@ pub enum BottomPaneCommand {
         vowel_diff: Option<f32>,
         consonant_diff: Option<f32>,
     },
+    /// pitch edit over several moras at once.
+    ///
+    /// `diffs` holds `(accent_phrase, mora, pitch_diff)`.
+    Pitches { diffs: Vec<(usize, usize, f32)> },
+    /// length edit over several phonemes at once, including pauses.
+    ///
+    /// `diffs` holds `(accent_phrase, phoneme, length_diff)`.
+    Lengths { diffs: Vec<(usize, Phoneme, f32)> },
+    /// replace pause after accent phrase. `None` removes it.
+    ///
+    /// `pause` holds previous pause after execution.
+    SetPause {
+        accent_phrase: usize,
+        pause: Option<api_schema::MoraInProject>,
+    },
+    /// set whether accent phrase is question.
+    ///
+    /// `is_interrogative` holds previous value after execution.
+    Interrogative {
+        accent_phrase: usize,
+        is_interrogative: Option<bool>,
+    },
+    /// swap vowel and pitch of mora to toggle devoicing.
+    ///
+    /// `vowel` and `pitch` hold previous values after execution.
+    Devoice {
+        accent_phrase: usize,
+        mora: usize,
+        vowel: String,
+        pitch: f32,
+    },
+}
+
+/// pitch of revoiced mora without voiced neighbour.
+const FALLBACK_PITCH: f32 = 5.5;
+
+/// length of inserted pause in seconds.
+const DEFAULT_PAUSE_LENGTH: f32 = 0.3;
+
+/// phoneme in accent phrase whose length is editable.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Phoneme {
+    Consonant(usize),
+    Vowel(usize),
+    /// pause after accent phrase.
+    Pause,
+}
+
+impl Phoneme {
+    pub fn length_mut(self, ap: &mut api_schema::AccentPhraseInProject) -> Option<&mut f32> {
+        match self {
+            Phoneme::Consonant(mora) => ap.moras.get_mut(mora)?.consonantLength.as_mut(),
+            Phoneme::Vowel(mora) => Some(&mut ap.moras.get_mut(mora)?.vowelLength),
+            Phoneme::Pause => Some(&mut ap.pause_mora.as_mut()?.vowelLength),
+        }
+    }
+}
+
+impl BottomPaneCommand {
+    /// insert pause of default length after `accent_phrase`.
+    pub fn insert_pause(accent_phrase: usize) -> Self {
+        BottomPaneCommand::SetPause {
+            accent_phrase,
+            pause: Some(api_schema::MoraInProject {
+                text: "、".to_owned(),
+                consonant: None,
+                consonantLength: None,
+                vowel: "pau".to_owned(),
+                vowelLength: DEFAULT_PAUSE_LENGTH,
+                pitch: 0.0,
+            }),
+        }
+    }
+
+    /// toggle devoicing of mora. `None` if vowel has no unvoiced form.
+    ///
+    /// engine writes unvoiced vowel in uppercase with zero pitch.
+    /// revoiced mora takes pitch of nearest voiced mora in the accent phrase.
+    pub fn toggle_devoice(
+        edit_targets: &[api_schema::AccentPhraseInProject],
+        accent_phrase: usize,
+        mora: usize,
+    ) -> Option<Self> {
+        let moras = &edit_targets.get(accent_phrase)?.moras;
+        let target = moras.get(mora)?;
+        let (vowel, pitch) = match target.vowel.as_str() {
+            "a" | "i" | "u" | "e" | "o" => (target.vowel.to_uppercase(), 0.0),
+            "A" | "I" | "U" | "E" | "O" => {
+                let before = moras[..mora].iter().rev();
+                let after = moras[mora + 1..].iter();
+                let pitch = before
+                    .chain(after)
+                    .map(|mora| mora.pitch)
+                    .find(|pitch| *pitch > 0.0)
+                    .unwrap_or(FALLBACK_PITCH);
+                (target.vowel.to_lowercase(), pitch)
+            }
+            _ => return None,
+        };
+        Some(BottomPaneCommand::Devoice {
+            accent_phrase,
+            mora,
+            vowel,
+            pitch,
+        })
+    }
+
+    /// accent phrases whose pitch and length should be recalculated after execution.
+    pub fn affected_accent_phrases(&self) -> Option<std::ops::Range<usize>> {
+        match self {
+            BottomPaneCommand::Concat { accent_phrase, .. }
+            | BottomPaneCommand::AccentPhrase { accent_phrase, .. } => {
+                Some(*accent_phrase..*accent_phrase + 1)
+            }
+            BottomPaneCommand::Split { accent_phrase, .. } => {
+                Some(*accent_phrase..*accent_phrase + 2)
+            }
+            _ => None,
+        }
+    }
 }
 
 impl Command for BottomPaneCommand {
@@ -136,31 +471,38 @@ impl Command for BottomPaneCommand {
             BottomPaneCommand::Concat {
                 accent_phrase: index,
                 length: _,
+                removed,
             } => {
                 if let Some(ai) = project.audioItems.get_mut(uuid) {
                     if let Some(aq) = &mut ai.query {
                         assert!(*index + 1 < aq.accentPhrases.len());
-                        let right_moras = aq.accentPhrases[*index + 1].moras.clone();
-                        aq.accentPhrases[*index]
-                            .moras
-                            .extend_from_slice(&right_moras);
-                        aq.accentPhrases.remove(*index + 1);
+                        let mut right = aq.accentPhrases.remove(*index + 1);
+                        let left = &mut aq.accentPhrases[*index];
+                        left.moras.append(&mut right.moras);
+                        // pause after right accent phrase follows concatenated one.
+                        std::mem::swap(&mut left.pause_mora, &mut right.pause_mora);
+                        std::mem::swap(&mut left.isInterrogative, &mut right.isInterrogative);
+                        *removed = Some(right);
                     }
                 }
             }
             BottomPaneCommand::Split {
                 accent_phrase: index,
                 mora,
+                prev_accent,
             } => {
                 if let Some(ai) = project.audioItems.get_mut(uuid) {
                     if let Some(aq) = &mut ai.query {
                         assert!(*index < aq.accentPhrases.len());
+                        let left = &mut aq.accentPhrases[*index];
+                        *prev_accent = left.accent;
                         let insert = crate::api_schema::AccentPhraseInProject {
-                            moras: aq.accentPhrases[*index].moras.split_off(*mora),
-                            accent: 0,
-                            pause_mora: None,
-                            isInterrogative: None,
+                            moras: left.moras.split_off(*mora),
+                            accent: 1,
+                            pause_mora: left.pause_mora.take(),
+                            isInterrogative: left.isInterrogative.take(),
                         };
+                        left.accent = left.accent.min(*mora as i32);
                         aq.accentPhrases.insert(*index + 1, insert);
                     }
                 }
@@ -187,6 +529,65 @@ impl Command for BottomPaneCommand {
                     }
                 }
             }
+            BottomPaneCommand::Pitches { diffs } => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    if let Some(aq) = &mut ai.query {
+                        for (accent_phrase, mora, pitch_diff) in diffs {
+                            aq.accentPhrases[*accent_phrase].moras[*mora].pitch += *pitch_diff;
+                        }
+                    }
+                }
+            }
+            BottomPaneCommand::Lengths { diffs } => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    if let Some(aq) = &mut ai.query {
+                        for (accent_phrase, phoneme, length_diff) in diffs {
+                            if let Some(length) =
+                                phoneme.length_mut(&mut aq.accentPhrases[*accent_phrase])
+                            {
+                                *length += *length_diff;
+                            }
+                        }
+                    }
+                }
+            }
+            BottomPaneCommand::SetPause {
+                accent_phrase,
+                pause,
+            } => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    if let Some(aq) = &mut ai.query {
+                        std::mem::swap(&mut aq.accentPhrases[*accent_phrase].pause_mora, pause);
+                    }
+                }
+            }
+            BottomPaneCommand::Interrogative {
+                accent_phrase,
+                is_interrogative,
+            } => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    if let Some(aq) = &mut ai.query {
+                        std::mem::swap(
+                            &mut aq.accentPhrases[*accent_phrase].isInterrogative,
+                            is_interrogative,
+                        );
+                    }
+                }
+            }
+            BottomPaneCommand::Devoice {
+                accent_phrase,
+                mora,
+                vowel,
+                pitch,
+            } => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    if let Some(aq) = &mut ai.query {
+                        let target = &mut aq.accentPhrases[*accent_phrase].moras[*mora];
+                        std::mem::swap(&mut target.vowel, vowel);
+                        std::mem::swap(&mut target.pitch, pitch);
+                    }
+                }
+            }
             BottomPaneCommand::VowelAndConsonant {
                 accent_phrase,
                 mora,
@@ -216,32 +617,35 @@ impl Command for BottomPaneCommand {
             BottomPaneCommand::Concat {
                 accent_phrase: index,
                 length,
+                removed,
             } => {
                 if let Some(ai) = project.audioItems.get_mut(uuid) {
                     if let Some(aq) = &mut ai.query {
                         assert!(*index < aq.accentPhrases.len());
-                        let insert = crate::api_schema::AccentPhraseInProject {
-                            moras: aq.accentPhrases[*index].moras.split_off(*length),
-                            accent: 0,
-                            pause_mora: None,
-                            isInterrogative: None,
-                        };
-                        aq.accentPhrases.insert(*index + 1, insert);
+                        if let Some(mut right) = removed.take() {
+                            let left = &mut aq.accentPhrases[*index];
+                            right.moras = left.moras.split_off(*length);
+                            std::mem::swap(&mut left.pause_mora, &mut right.pause_mora);
+                            std::mem::swap(&mut left.isInterrogative, &mut right.isInterrogative);
+                            aq.accentPhrases.insert(*index + 1, right);
+                        }
                     }
                 }
             }
             BottomPaneCommand::Split {
                 accent_phrase: index,
                 mora: _,
+                prev_accent,
             } => {
                 if let Some(ai) = project.audioItems.get_mut(uuid) {
                     if let Some(aq) = &mut ai.query {
                         assert!(*index + 1 < aq.accentPhrases.len());
-                        let right_moras = aq.accentPhrases[*index + 1].moras.clone();
-                        aq.accentPhrases[*index]
-                            .moras
-                            .extend_from_slice(&right_moras);
-                        aq.accentPhrases.remove(*index + 1);
+                        let mut right = aq.accentPhrases.remove(*index + 1);
+                        let left = &mut aq.accentPhrases[*index];
+                        left.moras.append(&mut right.moras);
+                        left.accent = *prev_accent;
+                        left.pause_mora = right.pause_mora;
+                        left.isInterrogative = right.isInterrogative;
                     }
                 }
             }
@@ -267,6 +671,65 @@ impl Command for BottomPaneCommand {
                     }
                 }
             }
+            BottomPaneCommand::Pitches { diffs } => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    if let Some(aq) = &mut ai.query {
+                        for (accent_phrase, mora, pitch_diff) in diffs {
+                            aq.accentPhrases[*accent_phrase].moras[*mora].pitch -= *pitch_diff;
+                        }
+                    }
+                }
+            }
+            BottomPaneCommand::Lengths { diffs } => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    if let Some(aq) = &mut ai.query {
+                        for (accent_phrase, phoneme, length_diff) in diffs {
+                            if let Some(length) =
+                                phoneme.length_mut(&mut aq.accentPhrases[*accent_phrase])
+                            {
+                                *length -= *length_diff;
+                            }
+                        }
+                    }
+                }
+            }
+            BottomPaneCommand::SetPause {
+                accent_phrase,
+                pause,
+            } => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    if let Some(aq) = &mut ai.query {
+                        std::mem::swap(&mut aq.accentPhrases[*accent_phrase].pause_mora, pause);
+                    }
+                }
+            }
+            BottomPaneCommand::Interrogative {
+                accent_phrase,
+                is_interrogative,
+            } => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    if let Some(aq) = &mut ai.query {
+                        std::mem::swap(
+                            &mut aq.accentPhrases[*accent_phrase].isInterrogative,
+                            is_interrogative,
+                        );
+                    }
+                }
+            }
+            BottomPaneCommand::Devoice {
+                accent_phrase,
+                mora,
+                vowel,
+                pitch,
+            } => {
+                if let Some(ai) = project.audioItems.get_mut(uuid) {
+                    if let Some(aq) = &mut ai.query {
+                        let target = &mut aq.accentPhrases[*accent_phrase].moras[*mora];
+                        std::mem::swap(&mut target.vowel, vowel);
+                        std::mem::swap(&mut target.pitch, pitch);
+                    }
+                }
+            }
             BottomPaneCommand::VowelAndConsonant {
                 accent_phrase,
                 mora,
@@ -291,17 +754,177 @@ impl Command for BottomPaneCommand {
         }
     }
 
+    fn to_record(&self) -> CommandRecord {
+        CommandRecord::BottomPane(self.clone())
+    }
+
+    fn merge(
+        &mut self,
+        own_uuid: &str,
+        next: &CommandRecord,
+        next_uuid: &str,
+        _project: &VoiceVoxProject,
+        elapsed: Duration,
+    ) -> bool {
+        if own_uuid != next_uuid || elapsed >= MERGE_WINDOW {
+            return false;
+        }
+        let next = if let CommandRecord::BottomPane(next) = next {
+            next
+        } else {
+            return false;
+        };
+        match (self, next) {
+            (
+                BottomPaneCommand::AccentPhrase {
+                    accent_phrase,
+                    new_accent,
+                    ..
+                },
+                BottomPaneCommand::AccentPhrase {
+                    accent_phrase: next_accent_phrase,
+                    new_accent: next_accent,
+                    ..
+                },
+            ) if accent_phrase == next_accent_phrase => {
+                *new_accent = *next_accent;
+                true
+            }
+            (
+                BottomPaneCommand::Pitch {
+                    accent_phrase,
+                    mora,
+                    pitch_diff,
+                },
+                BottomPaneCommand::Pitch {
+                    accent_phrase: next_accent_phrase,
+                    mora: next_mora,
+                    pitch_diff: next_diff,
+                },
+            ) if accent_phrase == next_accent_phrase && mora == next_mora => {
+                *pitch_diff += *next_diff;
+                true
+            }
+            (
+                BottomPaneCommand::VowelAndConsonant {
+                    accent_phrase,
+                    mora,
+                    vowel_diff,
+                    consonant_diff,
+                },
+                BottomPaneCommand::VowelAndConsonant {
+                    accent_phrase: next_accent_phrase,
+                    mora: next_mora,
+                    vowel_diff: next_vowel,
+                    consonant_diff: next_consonant,
+                },
+            ) if accent_phrase == next_accent_phrase && mora == next_mora => {
+                *vowel_diff = add_diff(*vowel_diff, *next_vowel);
+                *consonant_diff = add_diff(*consonant_diff, *next_consonant);
+                true
+            }
+            (BottomPaneCommand::Pitches { diffs }, BottomPaneCommand::Pitches { diffs: next })
+                if diffs
+                    .iter()
+                    .map(|(ap, m, _)| (ap, m))
+                    .eq(next.iter().map(|(ap, m, _)| (ap, m))) =>
+            {
+                for ((_, _, diff), (_, _, next_diff)) in diffs.iter_mut().zip(next) {
+                    *diff += *next_diff;
+                }
+                true
+            }
+            (BottomPaneCommand::Lengths { diffs }, BottomPaneCommand::Lengths { diffs: next })
+                if diffs
+                    .iter()
+                    .map(|(ap, p, _)| (ap, p))
+                    .eq(next.iter().map(|(ap, p, _)| (ap, p))) =>
+            {
+                for ((_, _, diff), (_, _, next_diff)) in diffs.iter_mut().zip(next) {
+                    *diff += *next_diff;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn fits(&self, project: &VoiceVoxProject, uuid: &str, undo: bool) -> bool {
+        let accent_phrases = match project
+            .audioItems
+            .get(uuid)
+            .and_then(|ai| ai.query.as_ref())
+        {
+            Some(aq) => &aq.accentPhrases,
+            None => return false,
+        };
+        let mora_exists = |accent_phrase: usize, mora: usize| matches!(accent_phrases.get(accent_phrase), Some(ap) if mora < ap.moras.len());
+        match self {
+            BottomPaneCommand::Concat {
+                accent_phrase,
+                length,
+                ..
+            } if undo => {
+                matches!(accent_phrases.get(*accent_phrase), Some(ap) if *length <= ap.moras.len())
+            }
+            BottomPaneCommand::Concat { accent_phrase, .. } => {
+                *accent_phrase + 1 < accent_phrases.len()
+            }
+            BottomPaneCommand::Split { accent_phrase, .. } if undo => {
+                *accent_phrase + 1 < accent_phrases.len()
+            }
+            BottomPaneCommand::Split {
+                accent_phrase,
+                mora,
+                ..
+            } => {
+                matches!(accent_phrases.get(*accent_phrase), Some(ap) if *mora <= ap.moras.len())
+            }
+            BottomPaneCommand::AccentPhrase { accent_phrase, .. }
+            | BottomPaneCommand::SetPause { accent_phrase, .. }
+            | BottomPaneCommand::Interrogative { accent_phrase, .. } => {
+                *accent_phrase < accent_phrases.len()
+            }
+            BottomPaneCommand::Pitch {
+                accent_phrase,
+                mora,
+                ..
+            }
+            | BottomPaneCommand::VowelAndConsonant {
+                accent_phrase,
+                mora,
+                ..
+            }
+            | BottomPaneCommand::Devoice {
+                accent_phrase,
+                mora,
+                ..
+            } => mora_exists(*accent_phrase, *mora),
+            BottomPaneCommand::Pitches { diffs } => diffs
+                .iter()
+                .all(|(accent_phrase, mora, _)| mora_exists(*accent_phrase, *mora)),
+            BottomPaneCommand::Lengths { diffs } => diffs
+                .iter()
+                .all(|(accent_phrase, _, _)| *accent_phrase < accent_phrases.len()),
+        }
+    }
+
     fn op_name(&self) -> &str {
         match self {
             BottomPaneCommand::Concat { .. } => "アクセントフレーズ連結",
             BottomPaneCommand::Split { .. } => "アクセントフレーズ分割",
             BottomPaneCommand::AccentPhrase { .. } => "アクセント位置変更",
-            BottomPaneCommand::Pitch { .. } => "ピッチ変更",
+            BottomPaneCommand::Pitch { .. } | BottomPaneCommand::Pitches { .. } => "ピッチ変更",
             BottomPaneCommand::VowelAndConsonant { .. } => "母音子音長さ変更",
+            BottomPaneCommand::Lengths { .. } => "長さ変更",
+            BottomPaneCommand::SetPause { .. } => "ポーズ編集",
+            BottomPaneCommand::Interrogative { .. } => "疑問文",
+            BottomPaneCommand::Devoice { .. } => "無声化",
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub enum AudioQueryEditCommand {
     SpeedScale(f32),
     PitchScale(f32),
@@ -311,6 +934,50 @@ pub enum AudioQueryEditCommand {
     PostPhonemeLength(f32),
 }
 
+impl AudioQueryEditCommand {
+    fn amount(&self) -> f32 {
+        match self {
+            AudioQueryEditCommand::SpeedScale(x)
+            | AudioQueryEditCommand::PitchScale(x)
+            | AudioQueryEditCommand::IntonationScale(x)
+            | AudioQueryEditCommand::VolumeScale(x)
+            | AudioQueryEditCommand::PrePhonemeLength(x)
+            | AudioQueryEditCommand::PostPhonemeLength(x) => *x,
+        }
+    }
+
+    /// current value of the parameter in `query`.
+    pub fn value_of(&self, query: &api_schema::AudioQueryInProject) -> f32 {
+        match self {
+            AudioQueryEditCommand::SpeedScale(_) => query.speedScale,
+            AudioQueryEditCommand::PitchScale(_) => query.pitchScale,
+            AudioQueryEditCommand::IntonationScale(_) => query.intonationScale,
+            AudioQueryEditCommand::VolumeScale(_) => query.volumeScale,
+            AudioQueryEditCommand::PrePhonemeLength(_) => query.prePhonemeLength,
+            AudioQueryEditCommand::PostPhonemeLength(_) => query.postPhonemeLength,
+        }
+    }
+
+    /// treat self as new value and convert into difference from `query`.
+    pub fn diff_from(&self, query: &api_schema::AudioQueryInProject) -> Self {
+        let diff = self.amount() - self.value_of(query);
+        match self {
+            AudioQueryEditCommand::SpeedScale(_) => AudioQueryEditCommand::SpeedScale(diff),
+            AudioQueryEditCommand::PitchScale(_) => AudioQueryEditCommand::PitchScale(diff),
+            AudioQueryEditCommand::IntonationScale(_) => {
+                AudioQueryEditCommand::IntonationScale(diff)
+            }
+            AudioQueryEditCommand::VolumeScale(_) => AudioQueryEditCommand::VolumeScale(diff),
+            AudioQueryEditCommand::PrePhonemeLength(_) => {
+                AudioQueryEditCommand::PrePhonemeLength(diff)
+            }
+            AudioQueryEditCommand::PostPhonemeLength(_) => {
+                AudioQueryEditCommand::PostPhonemeLength(diff)
+            }
+        }
+    }
+}
+
 impl Command for AudioQueryEditCommand {
     fn invoke(&mut self, project: &mut crate::VoiceVoxProject, uuid: &str) {
         if let Some(cell) = project.audioItems.get_mut(uuid) {
@@ -366,6 +1033,36 @@ impl Command for AudioQueryEditCommand {
         }
     }
 
+    fn to_record(&self) -> CommandRecord {
+        CommandRecord::AudioQueryEdit(self.clone())
+    }
+
+    fn merge(
+        &mut self,
+        own_uuid: &str,
+        next: &CommandRecord,
+        next_uuid: &str,
+        _project: &VoiceVoxProject,
+        elapsed: Duration,
+    ) -> bool {
+        if own_uuid != next_uuid || elapsed >= MERGE_WINDOW {
+            return false;
+        }
+        use AudioQueryEditCommand::*;
+        match (self, next) {
+            (SpeedScale(x), CommandRecord::AudioQueryEdit(SpeedScale(y)))
+            | (PitchScale(x), CommandRecord::AudioQueryEdit(PitchScale(y)))
+            | (IntonationScale(x), CommandRecord::AudioQueryEdit(IntonationScale(y)))
+            | (VolumeScale(x), CommandRecord::AudioQueryEdit(VolumeScale(y)))
+            | (PrePhonemeLength(x), CommandRecord::AudioQueryEdit(PrePhonemeLength(y)))
+            | (PostPhonemeLength(x), CommandRecord::AudioQueryEdit(PostPhonemeLength(y))) => {
+                *x += *y;
+                true
+            }
+            _ => false,
+        }
+    }
+
     fn op_name(&self) -> &str {
         match self {
             AudioQueryEditCommand::SpeedScale(_) => "話速",
@@ -377,3 +1074,310 @@ impl Command for AudioQueryEditCommand {
         }
     }
 }
+
+/// set or clear morphing of line.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MorphingCommand {
+    pub prev: Option<project::MorphingInfo>,
+    pub next: Option<project::MorphingInfo>,
+}
+
+impl Command for MorphingCommand {
+    fn invoke(&mut self, project: &mut VoiceVoxProject, uuid: &str) {
+        if let Some(ai) = project.audioItems.get_mut(uuid) {
+            ai.morphingInfo = self.next.clone();
+        }
+    }
+
+    fn undo(&mut self, project: &mut VoiceVoxProject, uuid: &str) {
+        if let Some(ai) = project.audioItems.get_mut(uuid) {
+            ai.morphingInfo = self.prev.clone();
+        }
+    }
+
+    fn to_record(&self) -> CommandRecord {
+        CommandRecord::Morphing(self.clone())
+    }
+
+    fn merge(
+        &mut self,
+        own_uuid: &str,
+        next: &CommandRecord,
+        next_uuid: &str,
+        _project: &VoiceVoxProject,
+        elapsed: Duration,
+    ) -> bool {
+        if let CommandRecord::Morphing(next) = next {
+            if own_uuid == next_uuid && elapsed < MERGE_WINDOW {
+                self.next = next.next.clone();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn op_name(&self) -> &str {
+        "モーフィング"
+    }
+}
+
+/// set interrogative upspeak of line.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UpspeakCommand {
+    pub prev: Option<bool>,
+    pub next: Option<bool>,
+}
+
+impl Command for UpspeakCommand {
+    fn invoke(&mut self, project: &mut VoiceVoxProject, uuid: &str) {
+        if let Some(ai) = project.audioItems.get_mut(uuid) {
+            ai.interrogativeUpspeak = self.next;
+        }
+    }
+
+    fn undo(&mut self, project: &mut VoiceVoxProject, uuid: &str) {
+        if let Some(ai) = project.audioItems.get_mut(uuid) {
+            ai.interrogativeUpspeak = self.prev;
+        }
+    }
+
+    fn to_record(&self) -> CommandRecord {
+        CommandRecord::Upspeak(self.clone())
+    }
+
+    fn op_name(&self) -> &str {
+        "疑問文の自動調整"
+    }
+}
+
+fn add_diff(a: Option<f32>, b: Option<f32>) -> Option<f32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// group of commands recorded as single history entry.
+///
+/// used for bulk operations and transactions.
+pub struct CompositeCommand {
+    pub name: String,
+    /// commands and uuid of target line in execution order.
+    pub commands: Vec<(Box<dyn Command>, String)>,
+}
+
+/// serializable form of [CompositeCommand].
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CompositeRecord {
+    pub name: String,
+    pub commands: Vec<(CommandRecord, String)>,
+}
+
+impl From<CompositeRecord> for CompositeCommand {
+    fn from(record: CompositeRecord) -> Self {
+        Self {
+            name: record.name,
+            commands: record
+                .commands
+                .into_iter()
+                .map(|(command, uuid)| (command.into_command(), uuid))
+                .collect(),
+        }
+    }
+}
+
+impl Command for CompositeCommand {
+    fn invoke(&mut self, project: &mut VoiceVoxProject, _uuid: &str) {
+        for (command, uuid) in self.commands.iter_mut() {
+            command.invoke(project, uuid);
+        }
+    }
+
+    fn undo(&mut self, project: &mut VoiceVoxProject, _uuid: &str) {
+        for (command, uuid) in self.commands.iter_mut().rev() {
+            command.undo(project, uuid);
+        }
+    }
+
+    fn redo(&mut self, project: &mut VoiceVoxProject, _uuid: &str) {
+        for (command, uuid) in self.commands.iter_mut() {
+            command.redo(project, uuid);
+        }
+    }
+
+    /// children are checked one by one on a copy of `project` as each depends on the previous.
+    fn fits(&self, project: &VoiceVoxProject, _uuid: &str, undo: bool) -> bool {
+        let mut project = project.clone();
+        let mut commands: Vec<_> = self
+            .commands
+            .iter()
+            .map(|(command, uuid)| (command.to_record().into_command(), uuid))
+            .collect();
+        if undo {
+            commands.reverse();
+        }
+        commands.into_iter().all(|(mut command, uuid)| {
+            let fits = command.fits(&project, uuid, undo);
+            if fits && undo {
+                command.undo(&mut project, uuid);
+            } else if fits {
+                command.redo(&mut project, uuid);
+            }
+            fits
+        })
+    }
+
+    fn to_record(&self) -> CommandRecord {
+        CommandRecord::Composite(CompositeRecord {
+            name: self.name.clone(),
+            commands: self
+                .commands
+                .iter()
+                .map(|(command, uuid)| (command.to_record(), uuid.clone()))
+                .collect(),
+        })
+    }
+
+    /// hold `next` if one of children targets same line.
+    fn absorb(
+        &mut self,
+        _own_uuid: &str,
+        next: Box<dyn Command>,
+        next_uuid: &str,
+    ) -> Result<(), Box<dyn Command>> {
+        if self.commands.iter().any(|(_, uuid)| uuid == next_uuid) {
+            self.commands.push((next, next_uuid.to_owned()));
+            Ok(())
+        } else {
+            Err(next)
+        }
+    }
+
+    fn op_name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        carry_over_accent_phrases, carry_over_kana_phrases, AudioQueryCommands, BottomPaneCommand,
+    };
+    use crate::history::Command;
+    use crate::project::test::sample_project;
+    use voice_vox_api::api_schema::{AccentPhrase, AccentPhraseInProject, Mora};
+
+    fn phrase(text: &str, pitch: f32) -> AccentPhrase {
+        AccentPhrase {
+            moras: text
+                .chars()
+                .map(|c| Mora {
+                    text: c.to_string(),
+                    consonant: None,
+                    consonant_length: None,
+                    vowel: "a".to_owned(),
+                    vowel_length: 0.1,
+                    pitch,
+                })
+                .collect(),
+            accent: 1,
+            pause_mora: None,
+            is_interrogative: Some(false),
+        }
+    }
+
+    #[test]
+    fn keep_unchanged_phrases() {
+        let prev: Vec<AccentPhraseInProject> = [phrase("アイ", 6.0), phrase("ウエ", 6.0)]
+            .into_iter()
+            .map(|ap| ap.into())
+            .collect();
+        let next = [phrase("カキ", 5.0), phrase("ウエ", 5.0)];
+        let merged = carry_over_accent_phrases(&prev, &next);
+        assert_eq!(merged[0].moras[0].pitch, 5.0);
+        assert_eq!(merged[1], prev[1]);
+
+        // accent moved by reading edit.
+        let mut next = [phrase("アイ", 5.0), phrase("ウエ", 5.0)];
+        next[0].accent = 2;
+        let merged = carry_over_kana_phrases(&prev, &next);
+        assert_eq!(merged[0].accent, 2);
+        assert_eq!(merged[0].moras[0].pitch, 5.0);
+        assert_eq!(merged[1], prev[1]);
+    }
+
+    #[test]
+    fn insert_and_move_lines() {
+        let mut project = sample_project();
+        let before = project.clone();
+        let item = project.audioItems["line"].clone();
+        let mut insert_b = AudioQueryCommands::Insert(0, item.clone());
+        insert_b.invoke(&mut project, "b");
+        let mut insert_c = AudioQueryCommands::Insert(1, item);
+        insert_c.invoke(&mut project, "c");
+        assert_eq!(project.audioKeys, ["b", "c", "line"]);
+        let mut move_line = AudioQueryCommands::Move { from: 0, to: 0 };
+        move_line.invoke(&mut project, "line");
+        assert_eq!(project.audioKeys, ["line", "b", "c"]);
+        move_line.undo(&mut project, "line");
+        assert_eq!(project.audioKeys, ["b", "c", "line"]);
+        insert_c.undo(&mut project, "c");
+        insert_b.undo(&mut project, "b");
+        assert!(project == before);
+    }
+
+    #[test]
+    fn undo_batched_pitch() {
+        let mut project = sample_project();
+        let before = project.clone();
+        let mut cmd = BottomPaneCommand::Pitches {
+            diffs: vec![(0, 0, 0.5)],
+        };
+        cmd.invoke(&mut project, "line");
+        let query = project.audioItems["line"].query.as_ref().unwrap();
+        assert_eq!(query.accentPhrases[0].moras[0].pitch, 5.5);
+        cmd.undo(&mut project, "line");
+        assert!(project == before);
+    }
+
+    #[test]
+    fn insert_and_remove_pause() {
+        let mut project = sample_project();
+        let before = project.clone();
+        let mut insert = BottomPaneCommand::insert_pause(0);
+        insert.invoke(&mut project, "line");
+        let query = project.audioItems["line"].query.as_ref().unwrap();
+        assert!(query.accentPhrases[0].pause_mora.is_some());
+        let mut remove = BottomPaneCommand::SetPause {
+            accent_phrase: 0,
+            pause: None,
+        };
+        remove.invoke(&mut project, "line");
+        remove.undo(&mut project, "line");
+        insert.undo(&mut project, "line");
+        assert!(project == before);
+    }
+
+    #[test]
+    fn toggle_devoice_and_undo() {
+        let mut project = sample_project();
+        let before = project.clone();
+        let query = project.audioItems["line"].query.clone().unwrap();
+        let mut devoice = BottomPaneCommand::toggle_devoice(&query.accentPhrases, 0, 0).unwrap();
+        devoice.invoke(&mut project, "line");
+        let query = project.audioItems["line"].query.clone().unwrap();
+        assert_eq!(query.accentPhrases[0].moras[0].vowel, "A");
+        assert_eq!(query.accentPhrases[0].moras[0].pitch, 0.0);
+
+        let mut voice = BottomPaneCommand::toggle_devoice(&query.accentPhrases, 0, 0).unwrap();
+        voice.invoke(&mut project, "line");
+        let query = project.audioItems["line"].query.as_ref().unwrap();
+        assert_eq!(query.accentPhrases[0].moras[0].vowel, "a");
+        assert!(query.accentPhrases[0].moras[0].pitch > 0.0);
+
+        voice.undo(&mut project, "line");
+        devoice.undo(&mut project, "line");
+        assert!(project == before);
+    }
+}
@@ -1,14 +1,25 @@
-use eframe::egui::Ui;
-enum CtxMenuOp {
+use eframe::egui::{Button, Ui};
+pub enum CtxMenuOp {
     Cut,
     Copy,
     Paste,
+    /// apply copied query onto line with same text.
+    PasteTuning,
     SelectAll,
 }
-fn create_context_menu(ui: &mut Ui) -> Option<CtxMenuOp> {
+/// `can_cut` is false while cut would remove every line.
+pub fn create_context_menu(
+    ui: &mut Ui,
+    can_cut: bool,
+    can_paste_tuning: bool,
+) -> Option<CtxMenuOp> {
     let mut op = None;
     ui.vertical(|ui| {
-        if ui.button("切り取り").clicked() {
+        if ui
+            .add_enabled(can_cut, Button::new("切り取り"))
+            .on_disabled_hover_text("最後の行は切り取れません")
+            .clicked()
+        {
             op = Some(CtxMenuOp::Cut);
         }
 
@@ -19,6 +30,12 @@ fn create_context_menu(ui: &mut Ui) -> Option<CtxMenuOp> {
         if ui.button("貼り付け").clicked() {
             op = Some(CtxMenuOp::Paste);
         }
+        if ui
+            .add_enabled(can_paste_tuning, Button::new("調整のみ貼り付け"))
+            .clicked()
+        {
+            op = Some(CtxMenuOp::PasteTuning);
+        }
         ui.separator();
         if ui.button("全選択").clicked() {
             op = Some(CtxMenuOp::SelectAll);
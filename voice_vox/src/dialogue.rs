@@ -11,6 +11,27 @@ pub struct ExitControl;
 
 pub struct AcceptControl;
 
+/// result of unsaved changes dialogue on exit.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum SaveOnExit {
+    Save,
+    Discard,
+    Cancel,
+}
+
+pub struct SaveOnExitControl;
+
+impl DialogueSelectable<SaveOnExit> for SaveOnExitControl {
+    fn layout(&self) -> Vec<Option<(SaveOnExit, &str)>> {
+        vec![
+            Some((SaveOnExit::Cancel, "キャンセル")),
+            None,
+            Some((SaveOnExit::Discard, "破棄して終了")),
+            Some((SaveOnExit::Save, "保存して終了")),
+        ]
+    }
+}
+
 impl DialogueSelectable<bool> for AcceptControl {
     fn layout(&self) -> Vec<Option<(bool, &str)>> {
         vec![None, Some((false, "いいえ")), Some((true, "はい"))]
@@ -48,7 +69,7 @@ impl<'a, 'control, T: Clone + PartialEq> Dialogue<'a, 'control, T> {
                 ui.label(self.text);
                 ui.horizontal(|ui| {
                     let layout = self.control_constructor.layout();
-                    let mut split = layout.split(|x| *x == None);
+                    let mut split = layout.split(|x| x.is_none());
                     for v in split.next().unwrap() {
                         let v = v.clone();
                         if let Some((v, txt)) = v {
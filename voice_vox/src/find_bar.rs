@@ -0,0 +1,173 @@
+use crate::project::VoiceVoxProject;
+use eframe::egui::{Button, Color32, Key, TextEdit, Ui};
+use regex::{NoExpand, Regex};
+
+pub enum FindOp {
+    /// focus line of hit.
+    Jump(String),
+    /// (line, new text) of lines changed by replacement.
+    ReplaceAll(Vec<(String, String)>),
+}
+
+/// find and replace over text of all lines.
+#[derive(Default)]
+pub struct FindBar {
+    pub open: bool,
+    query: String,
+    replacement: String,
+    use_regex: bool,
+    /// compiled `query`. plain text is escaped.
+    pattern: Option<Regex>,
+    error: Option<String>,
+    /// line of last jumped hit.
+    current: Option<String>,
+}
+
+fn compile(query: &str, use_regex: bool) -> Result<Option<Regex>, regex::Error> {
+    if query.is_empty() {
+        Ok(None)
+    } else if use_regex {
+        Regex::new(query).map(Some)
+    } else {
+        Regex::new(&regex::escape(query)).map(Some)
+    }
+}
+
+/// new text of lines changed by replacing every match of `pattern`.
+///
+/// `$1` or `${name}` in `replacement` refer captures only in regex mode.
+fn replace_all(
+    project: &VoiceVoxProject,
+    pattern: &Regex,
+    replacement: &str,
+    use_regex: bool,
+) -> Vec<(String, String)> {
+    project
+        .audioKeys
+        .iter()
+        .filter_map(|line| {
+            let text = &project.audioItems.get(line)?.text;
+            let replaced = if use_regex {
+                pattern.replace_all(text, replacement)
+            } else {
+                pattern.replace_all(text, NoExpand(replacement))
+            };
+            if replaced == *text {
+                None
+            } else {
+                Some((line.clone(), replaced.into_owned()))
+            }
+        })
+        .collect()
+}
+
+impl FindBar {
+    /// pattern to highlight lines with. `None` while closed.
+    pub fn pattern(&self) -> Option<&Regex> {
+        if self.open {
+            self.pattern.as_ref()
+        } else {
+            None
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, project: &VoiceVoxProject) -> Option<FindOp> {
+        let mut op = None;
+        let hits: Vec<&String> = match &self.pattern {
+            Some(pattern) => project
+                .audioKeys
+                .iter()
+                .filter(|line| {
+                    matches!(project.audioItems.get(*line), Some(ai) if pattern.is_match(&ai.text))
+                })
+                .collect(),
+            None => vec![],
+        };
+        let position = self
+            .current
+            .as_ref()
+            .and_then(|current| hits.iter().position(|line| *line == current));
+        ui.horizontal(|ui| {
+            ui.label("検索");
+            let query = ui.add(TextEdit::singleline(&mut self.query).desired_width(160.0));
+            let use_regex = ui.checkbox(&mut self.use_regex, "正規表現");
+            if query.changed() || use_regex.changed() {
+                match compile(&self.query, self.use_regex) {
+                    Ok(pattern) => {
+                        self.pattern = pattern;
+                        self.error = None;
+                    }
+                    Err(e) => {
+                        self.pattern = None;
+                        self.error = Some(e.to_string());
+                    }
+                }
+            }
+            match position {
+                Some(position) => ui.label(format!("{}/{}件", position + 1, hits.len())),
+                None => ui.label(format!("{}件", hits.len())),
+            };
+            let enter = query.lost_focus() && ui.input().key_pressed(Key::Enter);
+            let previous = ui.add_enabled(!hits.is_empty(), Button::new("▲"));
+            let next = ui.add_enabled(!hits.is_empty(), Button::new("▼"));
+            let jump_to = if previous.clicked() {
+                Some(position.unwrap_or(0) + hits.len() - 1)
+            } else if next.clicked() || enter {
+                Some(position.map_or(0, |position| position + 1))
+            } else {
+                None
+            };
+            if let Some(line) = jump_to.and_then(|i| hits.get(i % hits.len().max(1))) {
+                self.current = Some((*line).clone());
+                op = Some(FindOp::Jump((*line).clone()));
+            }
+
+            ui.separator();
+            ui.label("置換");
+            ui.add(TextEdit::singleline(&mut self.replacement).desired_width(160.0));
+            if ui
+                .add_enabled(!hits.is_empty(), Button::new("すべて置換"))
+                .clicked()
+            {
+                if let Some(pattern) = &self.pattern {
+                    let changes = replace_all(project, pattern, &self.replacement, self.use_regex);
+                    if !changes.is_empty() {
+                        op = Some(FindOp::ReplaceAll(changes));
+                    }
+                }
+            }
+            if ui.button("×").clicked() {
+                self.open = false;
+            }
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+        op
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compile, replace_all};
+    use crate::project::test::sample_project;
+
+    #[test]
+    fn replace_plain_and_regex() {
+        let mut project = sample_project();
+        project.audioItems.get_mut("line").unwrap().text = "ずんだ餅とずんだ茶".to_owned();
+
+        let pattern = compile("ずんだ", false).unwrap().unwrap();
+        let changes = replace_all(&project, &pattern, "$1", false);
+        assert_eq!(changes, [("line".to_owned(), "$1餅と$1茶".to_owned())]);
+
+        let pattern = compile("ずんだ(.)", true).unwrap().unwrap();
+        let changes = replace_all(&project, &pattern, "${1}", true);
+        assert_eq!(changes, [("line".to_owned(), "餅と茶".to_owned())]);
+
+        assert!(compile("(", true).is_err());
+        assert!(compile("(", false).is_ok());
+        let pattern = compile("無い", false).unwrap().unwrap();
+        assert!(replace_all(&project, &pattern, "", false).is_empty());
+    }
+}
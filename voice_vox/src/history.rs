@@ -1,5 +1,18 @@
-use crate::project::VoiceVoxProject;
+use crate::chara_change_button::CharaChangeCommand;
+use crate::commands::{
+    AudioQueryCommands, AudioQueryEditCommand, BottomPaneCommand, CompositeCommand,
+    CompositeRecord, MorphingCommand, UpspeakCommand,
+};
+use crate::project::{VoiceVoxProject, VoiceVoxProjectFile};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// consecutive edits within this duration are merged into one history entry.
+pub const MERGE_WINDOW: Duration = Duration::from_secs(2);
+
+/// identifies history entry while application is running. not stored in project file.
+pub type EntryId = u64;
 
 /// an interface for undo/redo operations.
 pub trait Command {
@@ -13,22 +26,177 @@ pub trait Command {
     fn redo(&mut self, project: &mut crate::VoiceVoxProject, uuid: &str) {
         self.invoke(project, uuid)
     }
+    /// convert into serializable form to store into project file.
+    fn to_record(&self) -> CommandRecord;
+    /// try to absorb `next` which is already executed.
+    ///
+    /// * `own_uuid` : target line of self.
+    /// * `next_uuid` : target line of next.
+    /// * `elapsed` : duration from last execution of self.
+    ///
+    /// returns true if merged. then `next` is dropped.
+    fn merge(
+        &mut self,
+        _own_uuid: &str,
+        _next: &CommandRecord,
+        _next_uuid: &str,
+        _project: &VoiceVoxProject,
+        _elapsed: Duration,
+    ) -> bool {
+        false
+    }
+    /// take `next` which is already executed as a part of self.
+    ///
+    /// returns `next` back if self can not hold it.
+    fn absorb(
+        &mut self,
+        _own_uuid: &str,
+        next: Box<dyn Command>,
+        _next_uuid: &str,
+    ) -> Result<(), Box<dyn Command>> {
+        Err(next)
+    }
     /// used for history manager.
     fn op_name(&self) -> &str {
         "unnamed"
     }
+    /// whether line and indices referred by self exist in `project`.
+    ///
+    /// checked before undo (`undo` is true) or redo of history restored from file.
+    fn fits(&self, project: &VoiceVoxProject, uuid: &str, _undo: bool) -> bool {
+        project.audioItems.contains_key(uuid)
+    }
+}
+
+/// serializable form of [Command].
+#[derive(Serialize, Deserialize, Clone)]
+pub enum CommandRecord {
+    AudioQuery(AudioQueryCommands),
+    BottomPane(BottomPaneCommand),
+    AudioQueryEdit(AudioQueryEditCommand),
+    CharaChange(CharaChangeCommand),
+    Morphing(MorphingCommand),
+    Upspeak(UpspeakCommand),
+    Composite(CompositeRecord),
+}
+
+impl CommandRecord {
+    pub fn into_command(self) -> Box<dyn Command> {
+        match self {
+            CommandRecord::AudioQuery(x) => Box::new(x),
+            CommandRecord::BottomPane(x) => Box::new(x),
+            CommandRecord::AudioQueryEdit(x) => Box::new(x),
+            CommandRecord::CharaChange(x) => Box::new(x),
+            CommandRecord::Morphing(x) => Box::new(x),
+            CommandRecord::Upspeak(x) => Box::new(x),
+            CommandRecord::Composite(x) => Box::new(CompositeCommand::from(x)),
+        }
+    }
+}
+
+/// serializable form of [HistoryEntry].
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryRecord {
+    pub command: CommandRecord,
+    pub uuid: String,
+    pub time: SystemTime,
+}
+
+/// undo/redo stacks stored in history file next to project file.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ProjectHistory {
+    pub undo_stack: Vec<HistoryRecord>,
+    pub redo_stack: Vec<HistoryRecord>,
+}
+
+impl ProjectHistory {
+    /// whether every entry can be undone from `project` and redone again.
+    ///
+    /// history edited by hand or saved with other version of project is rejected.
+    pub fn fits(&self, project: &VoiceVoxProject) -> bool {
+        let mut project = project.clone();
+        let mut undo_stack: Vec<_> = self
+            .undo_stack
+            .iter()
+            .map(|record| (record.command.clone().into_command(), &record.uuid))
+            .collect();
+        for (command, uuid) in undo_stack.iter_mut().rev() {
+            if !command.fits(&project, uuid, true) {
+                return false;
+            }
+            command.undo(&mut project, uuid);
+        }
+        let redo_stack = self
+            .redo_stack
+            .iter()
+            .rev()
+            .map(|record| (record.command.clone().into_command(), &record.uuid));
+        for (mut command, uuid) in undo_stack.into_iter().chain(redo_stack) {
+            if !command.fits(&project, uuid, false) {
+                return false;
+            }
+            command.redo(&mut project, uuid);
+        }
+        true
+    }
+}
+
+/// an entry of undo/redo stack.
+pub struct HistoryEntry {
+    pub command: Box<dyn Command>,
+    /// uuid of target line.
+    pub uuid: String,
+    /// when command invoked.
+    pub time: SystemTime,
+    /// used by engine results to find the edit which requested them.
+    pub id: EntryId,
+}
+
+impl HistoryEntry {
+    /// take `command` as a part of self. `command` is wrapped with self if self can not absorb it.
+    fn fold(&mut self, command: Box<dyn Command>, uuid: String) {
+        if let Err(command) = self.command.absorb(&self.uuid, command, &uuid) {
+            let name = self.command.op_name().to_owned();
+            let own = std::mem::replace(
+                &mut self.command,
+                Box::new(CompositeCommand {
+                    name: String::new(),
+                    commands: vec![],
+                }),
+            );
+            self.command = Box::new(CompositeCommand {
+                name,
+                commands: vec![(own, self.uuid.clone()), (command, uuid)],
+            });
+        }
+    }
+}
+
+impl From<&HistoryEntry> for HistoryRecord {
+    fn from(entry: &HistoryEntry) -> Self {
+        Self {
+            command: entry.command.to_record(),
+            uuid: entry.uuid.clone(),
+            time: entry.time,
+        }
+    }
 }
 
 /// manage some histories.
 /// * manage undo/redo operations
-/// * manage last update time for each line.
 ///
 pub struct HistoryManager {
-    undo_stack: Vec<(Box<dyn Command>, String)>,
-    redo_stack: Vec<(Box<dyn Command>, String)>,
-    update_times: HashMap<String, (Vec<tokio::time::Instant>, usize)>,
+    undo_stack: Vec<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
     pub project: crate::VoiceVoxProject,
     last_saved_snapshot: Option<crate::VoiceVoxProject>,
+    /// commands invoked between begin and commit with id of entry to be committed.
+    transaction: Option<(EntryId, CompositeCommand)>,
+    next_id: EntryId,
+    /// last entry of undo stack was invoked directly and accepts merge of next edit.
+    ///
+    /// reset by undo, redo and transactions so that new edit is not merged into older entry.
+    mergeable: bool,
 }
 
 impl HistoryManager {
@@ -43,104 +211,269 @@ impl HistoryManager {
                 styleId: 2,
                 query: Some(crate::BLANK_AUDIO_QUERY.get().unwrap().clone().into()),
                 presetKey: None,
+                morphingInfo: None,
+                interrogativeUpspeak: None,
             },
         );
+        let project = VoiceVoxProject {
+            appVersion: "0.11.4".to_string(),
+            audioKeys: vec![dummy],
+            audioItems: items,
+        };
         Self {
             undo_stack: vec![],
             redo_stack: vec![],
-            update_times: Default::default(),
-            project: VoiceVoxProject {
-                appVersion: "0.11.4".to_string(),
-                audioKeys: vec![dummy],
-                audioItems: items,
-            },
-            last_saved_snapshot: None,
+            last_saved_snapshot: Some(project.clone()),
+            project,
+            transaction: None,
+            next_id: 0,
+            mergeable: false,
         }
     }
-    pub fn from_project(project: VoiceVoxProject) -> Self {
+    /// restore project and undo/redo stacks from project file.
+    pub fn from_project_file(file: VoiceVoxProjectFile) -> Self {
+        let mut next_id = 0;
+        let mut restore = |stack: Vec<HistoryRecord>| -> Vec<HistoryEntry> {
+            stack
+                .into_iter()
+                .map(|record| {
+                    next_id += 1;
+                    HistoryEntry {
+                        command: record.command.into_command(),
+                        uuid: record.uuid,
+                        time: record.time,
+                        id: next_id,
+                    }
+                })
+                .collect()
+        };
+        let (undo_stack, redo_stack) = match file.history {
+            Some(history) if history.fits(&file.project) => {
+                (restore(history.undo_stack), restore(history.redo_stack))
+            }
+            Some(_) => {
+                log::warn!("ignore history which does not match project");
+                (vec![], vec![])
+            }
+            None => (vec![], vec![]),
+        };
         Self {
-            undo_stack: vec![],
-            redo_stack: vec![],
-            update_times: Default::default(),
-            project,
-            last_saved_snapshot: None,
+            undo_stack,
+            redo_stack,
+            last_saved_snapshot: Some(file.project.clone()),
+            project: file.project,
+            transaction: None,
+            next_id,
+            mergeable: false,
+        }
+    }
+
+    fn new_id(&mut self) -> EntryId {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// create project file contents with undo/redo stacks.
+    pub fn to_project_file(&self) -> VoiceVoxProjectFile {
+        let record = |stack: &[HistoryEntry]| stack.iter().map(HistoryRecord::from).collect();
+        let history = if self.is_empty() {
+            None
+        } else {
+            Some(ProjectHistory {
+                undo_stack: record(&self.undo_stack),
+                redo_stack: record(&self.redo_stack),
+            })
+        };
+        VoiceVoxProjectFile {
+            project: self.project.clone(),
+            history,
         }
     }
     /// execute command and record to undo stack.
-    pub fn invoke(&mut self, mut command: Box<dyn Command>, uuid: String) {
+    ///
+    /// * while transaction is open, command is recorded into the transaction.
+    /// * otherwise command is merged into last entry if possible.
+    ///
+    /// returns id of entry which holds the command.
+    pub fn invoke(&mut self, mut command: Box<dyn Command>, uuid: String) -> EntryId {
         command.invoke(&mut self.project, &uuid);
         self.redo_stack.clear();
-        let now = tokio::time::Instant::now();
+        if let Some((id, transaction)) = &mut self.transaction {
+            log::debug!("exec {} in {}", command.op_name(), transaction.name);
+            transaction.commands.push((command, uuid));
+            return *id;
+        }
+        if let Some(id) = self.merge_into_last(command.as_ref(), &uuid) {
+            log::debug!("merged {}", command.op_name());
+            return id;
+        }
+        log::debug!("exec {}", command.op_name());
+        let id = self.new_id();
+        self.undo_stack.push(HistoryEntry {
+            command,
+            uuid,
+            time: SystemTime::now(),
+            id,
+        });
+        self.mergeable = true;
+        id
+    }
+
+    /// record engine result `command` as a part of entry `entry` which requested it.
+    ///
+    /// * entry in undo stack : command is executed now.
+    /// * entry in redo stack : command is executed when the entry is redone.
+    /// * otherwise entry is dropped by new edit and command is discarded.
+    ///
+    /// redo stack is kept as is.
+    pub fn amend(&mut self, mut command: Box<dyn Command>, uuid: String, entry: EntryId) {
+        if let Some((id, transaction)) = &mut self.transaction {
+            if *id == entry {
+                command.invoke(&mut self.project, &uuid);
+                transaction.commands.push((command, uuid));
+                return;
+            }
+        }
+        if let Some(target) = self.undo_stack.iter_mut().find(|e| e.id == entry) {
+            command.invoke(&mut self.project, &uuid);
+            log::debug!(
+                "amend {} with {}",
+                target.command.op_name(),
+                command.op_name()
+            );
+            target.fold(command, uuid);
+        } else if let Some(target) = self.redo_stack.iter_mut().find(|e| e.id == entry) {
+            log::debug!(
+                "amend undone {} with {}",
+                target.command.op_name(),
+                command.op_name()
+            );
+            target.fold(command, uuid);
+        } else {
+            log::debug!("discard {} for dropped entry", command.op_name());
+        }
+    }
 
-        if let Some((times, cursor)) = self.update_times.get_mut(&uuid) {
-            times.push(now);
-            *cursor += 1;
-            log::debug!("{} revision {}", uuid, cursor);
+    fn merge_into_last(&mut self, command: &dyn Command, uuid: &str) -> Option<EntryId> {
+        if !self.mergeable {
+            return None;
+        }
+        let last = self.undo_stack.last_mut()?;
+        let now = SystemTime::now();
+        let elapsed = now.duration_since(last.time).unwrap_or_default();
+        let merged = last.command.merge(
+            &last.uuid,
+            &command.to_record(),
+            uuid,
+            &self.project,
+            elapsed,
+        );
+        if merged {
+            last.time = now;
+            Some(last.id)
         } else {
-            self.update_times.insert(uuid.clone(), (vec![now], 0));
+            None
         }
+    }
 
-        log::debug!("exec {}", command.op_name());
-        self.undo_stack.push((command, uuid));
+    /// start grouping commands into single history entry named `name`.
+    pub fn begin_transaction(&mut self, name: &str) {
+        if let Some((_, transaction)) = &self.transaction {
+            log::warn!("transaction {} is already open", transaction.name);
+        } else {
+            let id = self.new_id();
+            self.transaction = Some((
+                id,
+                CompositeCommand {
+                    name: name.to_owned(),
+                    commands: vec![],
+                },
+            ));
+        }
     }
 
-    pub fn undo(&mut self) {
-        if let Some((mut op, uuid)) = self.undo_stack.pop() {
-            op.undo(&mut self.project, &uuid);
-            if let Some((_times, cursor)) = self.update_times.get_mut(&uuid) {
-                if *cursor > 0 {
-                    *cursor -= 1;
-                    log::debug!("{} revision {}", uuid, cursor);
-                }
+    /// record commands invoked since [Self::begin_transaction] as single entry.
+    pub fn commit_transaction(&mut self) {
+        if let Some((id, transaction)) = self.transaction.take() {
+            if let Some((_, uuid)) = transaction.commands.first() {
+                let uuid = uuid.clone();
+                log::debug!("commit {}", transaction.name);
+                self.undo_stack.push(HistoryEntry {
+                    command: Box::new(transaction),
+                    uuid,
+                    time: SystemTime::now(),
+                    id,
+                });
+                self.mergeable = false;
             }
-            log::debug!("revert {}", op.op_name());
-            self.redo_stack.push((op, uuid));
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(mut entry) = self.undo_stack.pop() {
+            let uuid = &entry.uuid;
+            entry.command.undo(&mut self.project, uuid);
+            log::debug!("revert {}", entry.command.op_name());
+            self.redo_stack.push(entry);
+            self.mergeable = false;
         } else {
             log::debug!("no more in undo stack")
         }
     }
 
     pub fn redo(&mut self) {
-        if let Some((mut op, uuid)) = self.redo_stack.pop() {
-            op.redo(&mut self.project, &uuid);
-            if let Some((times, cursor)) = self.update_times.get_mut(&uuid) {
-                if *cursor < times.len() {
-                    *cursor += 1;
-                    log::debug!("{} revision {}", uuid, cursor);
-                }
-            }
-            log::debug!("redo {}", op.op_name());
-            self.undo_stack.push((op, uuid));
+        if let Some(mut entry) = self.redo_stack.pop() {
+            let uuid = &entry.uuid;
+            entry.command.redo(&mut self.project, uuid);
+            log::debug!("redo {}", entry.command.op_name());
+            self.undo_stack.push(entry);
+            self.mergeable = false;
         } else {
             log::debug!("no more in redo stack.");
         }
     }
 
-    pub fn get_current_time_stamp(&self, uuid: &str) -> Option<tokio::time::Instant> {
-        self.update_times
-            .get(uuid)
-            .map(|(times, cursor)| times[*cursor])
+    /// undo `count` times.
+    pub fn undo_n(&mut self, count: usize) {
+        for _ in 0..count {
+            self.undo();
+        }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.undo_stack.is_empty() && self.redo_stack.is_empty()
+    /// redo `count` times.
+    pub fn redo_n(&mut self, count: usize) {
+        for _ in 0..count {
+            self.redo();
+        }
     }
 
-    pub fn undoable(&self) -> bool {
-        !self.undo_stack.is_empty()
+    /// drop all undo/redo entries. project itself is not changed.
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.mergeable = false;
+        log::debug!("history cleared");
     }
 
-    pub fn redoable(&self) -> bool {
-        !self.redo_stack.is_empty()
+    /// entries of undo stack. oldest first.
+    pub fn undo_stack(&self) -> &[HistoryEntry] {
+        &self.undo_stack
+    }
+
+    /// entries of redo stack. next redo is last.
+    pub fn redo_stack(&self) -> &[HistoryEntry] {
+        &self.redo_stack
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.undo_stack.is_empty() && self.redo_stack.is_empty()
     }
 
     pub fn saved(&self) -> bool {
         if let Some(ss) = &self.last_saved_snapshot {
             ss == &self.project
-        } else if self.is_empty() {
-            true
         } else {
-            false
+            self.is_empty()
         }
     }
 
@@ -148,3 +481,165 @@ impl HistoryManager {
         self.last_saved_snapshot = Some(self.project.clone());
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Command, EntryId, HistoryManager};
+    use crate::chara_change_button::CharaChangeCommand;
+    use crate::commands::{AudioQueryCommands, AudioQueryEditCommand};
+    use crate::project::test::sample_project;
+    use crate::project::VoiceVoxProjectFile;
+
+    fn manager() -> HistoryManager {
+        HistoryManager::from_project_file(VoiceVoxProjectFile {
+            project: sample_project(),
+            history: None,
+        })
+    }
+
+    fn speed_scale(histories: &HistoryManager) -> f32 {
+        histories.project.audioItems["line"]
+            .query
+            .as_ref()
+            .unwrap()
+            .speedScale
+    }
+
+    #[test]
+    fn merge_consecutive_edits() {
+        let mut histories = manager();
+        for _ in 0..3 {
+            histories.invoke(
+                Box::new(AudioQueryEditCommand::SpeedScale(0.25)),
+                "line".to_string(),
+            );
+        }
+        histories.invoke(
+            Box::new(AudioQueryEditCommand::PitchScale(0.1)),
+            "line".to_string(),
+        );
+        assert_eq!(histories.undo_stack().len(), 2);
+        assert_eq!(speed_scale(&histories), 1.75);
+        histories.undo_n(2);
+        assert!(histories.project == sample_project());
+    }
+
+    #[test]
+    fn transaction_is_single_entry() {
+        let mut histories = manager();
+        histories.begin_transaction("一括変更");
+        histories.invoke(
+            Box::new(AudioQueryEditCommand::SpeedScale(0.5)),
+            "line".to_string(),
+        );
+        histories.invoke(
+            Box::new(AudioQueryEditCommand::PitchScale(0.1)),
+            "line".to_string(),
+        );
+        histories.commit_transaction();
+        assert_eq!(histories.undo_stack().len(), 1);
+        assert_eq!(histories.undo_stack()[0].command.op_name(), "一括変更");
+        histories.undo();
+        assert!(histories.project == sample_project());
+        histories.redo();
+        assert_eq!(speed_scale(&histories), 1.5);
+    }
+
+    fn chara_change(histories: &mut HistoryManager) -> EntryId {
+        histories.invoke(
+            Box::new(CharaChangeCommand {
+                prev_chara: 2,
+                new_chara: 3,
+            }),
+            "line".to_string(),
+        )
+    }
+
+    fn mora_data() -> Box<AudioQueryCommands> {
+        let mut accent_phrases = sample_project().audioItems["line"]
+            .query
+            .as_ref()
+            .unwrap()
+            .accentPhrases
+            .clone();
+        accent_phrases[0].moras[0].pitch = 6.0;
+        Box::new(AudioQueryCommands::UpdateMoraData {
+            start: 0,
+            accent_phrases,
+        })
+    }
+
+    #[test]
+    fn no_merge_after_undo_or_transaction() {
+        let mut histories = manager();
+        let speed = || Box::new(AudioQueryEditCommand::SpeedScale(0.25));
+        histories.invoke(speed(), "line".to_string());
+        histories.undo();
+        histories.redo();
+        histories.invoke(speed(), "line".to_string());
+        assert_eq!(histories.undo_stack().len(), 2);
+
+        histories.begin_transaction("一括変更");
+        histories.invoke(speed(), "line".to_string());
+        histories.commit_transaction();
+        histories.invoke(speed(), "line".to_string());
+        assert_eq!(histories.undo_stack().len(), 4);
+        assert_eq!(speed_scale(&histories), 2.0);
+    }
+
+    #[test]
+    fn amend_undone_or_dropped_entry() {
+        let mut histories = manager();
+        let entry = chara_change(&mut histories);
+        histories.undo();
+        histories.amend(mora_data(), "line".to_string(), entry);
+        assert!(histories.project == sample_project());
+        assert_eq!(histories.redo_stack().len(), 1);
+        histories.redo();
+        assert_eq!(
+            histories.project.audioItems["line"]
+                .query
+                .as_ref()
+                .unwrap()
+                .accentPhrases[0]
+                .moras[0]
+                .pitch,
+            6.0
+        );
+
+        histories.undo();
+        histories.invoke(
+            Box::new(AudioQueryEditCommand::SpeedScale(0.5)),
+            "line".to_string(),
+        );
+        histories.amend(mora_data(), "line".to_string(), entry);
+        assert_eq!(histories.undo_stack().len(), 1);
+        assert_eq!(
+            histories.undo_stack()[0].command.op_name(),
+            AudioQueryEditCommand::SpeedScale(0.5).op_name()
+        );
+        histories.undo();
+        assert!(histories.project == sample_project());
+    }
+
+    #[test]
+    fn amend_chara_change_with_mora_data() {
+        let mut histories = manager();
+        let entry = chara_change(&mut histories);
+        histories.amend(mora_data(), "line".to_string(), entry);
+        assert_eq!(histories.undo_stack().len(), 1);
+        assert_eq!(
+            histories.undo_stack()[0].command.op_name(),
+            "キャラクター変更"
+        );
+        histories.undo();
+        assert!(histories.project == sample_project());
+        histories.redo();
+        let item = &histories.project.audioItems["line"];
+        assert_eq!(item.styleId, 3);
+        assert_eq!(
+            item.query.as_ref().unwrap().accentPhrases[0].moras[0].pitch,
+            6.0
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crate::history::{HistoryEntry, HistoryManager};
+use eframe::egui;
+use eframe::egui::{RichText, Ui};
+use std::time::SystemTime;
+
+pub enum HistoryPanelOp {
+    /// undo given times.
+    Undo(usize),
+    /// redo given times.
+    Redo(usize),
+    Clear,
+}
+
+/// list undo/redo entries.
+///
+/// clicking an entry rewinds or replays history up to the entry.
+pub fn create_history_panel(ui: &mut Ui, histories: &HistoryManager) -> Option<HistoryPanelOp> {
+    let mut op = None;
+    let undo_stack = histories.undo_stack();
+    let redo_stack = histories.redo_stack();
+    ui.vertical(|ui| {
+        if ui
+            .add_enabled(!histories.is_empty(), egui::Button::new("履歴を消去"))
+            .clicked()
+        {
+            op = Some(HistoryPanelOp::Clear);
+        }
+        ui.separator();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            if ui
+                .selectable_label(undo_stack.is_empty(), "初期状態")
+                .clicked()
+                && !undo_stack.is_empty()
+            {
+                op = Some(HistoryPanelOp::Undo(undo_stack.len()));
+            }
+            for (index, entry) in undo_stack.iter().enumerate() {
+                let current = index + 1 == undo_stack.len();
+                if ui
+                    .selectable_label(current, entry_label(entry, histories))
+                    .clicked()
+                    && !current
+                {
+                    op = Some(HistoryPanelOp::Undo(undo_stack.len() - index - 1));
+                }
+            }
+            // top of redo stack is the next state.
+            for (index, entry) in redo_stack.iter().enumerate().rev() {
+                let label = RichText::new(entry_label(entry, histories)).weak();
+                if ui.selectable_label(false, label).clicked() {
+                    op = Some(HistoryPanelOp::Redo(redo_stack.len() - index));
+                }
+            }
+        });
+    });
+    op
+}
+
+fn entry_label(entry: &HistoryEntry, histories: &HistoryManager) -> String {
+    let text = histories
+        .project
+        .audioItems
+        .get(&entry.uuid)
+        .map(|ai| ai.text.as_str())
+        .unwrap_or_default();
+    format!(
+        "{} 「{}」 {}",
+        entry.command.op_name(),
+        text,
+        elapsed_text(entry.time)
+    )
+}
+
+fn elapsed_text(time: SystemTime) -> String {
+    let secs = SystemTime::now()
+        .duration_since(time)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    match secs {
+        0..=9 => "たった今".to_owned(),
+        10..=59 => format!("{}秒前", secs),
+        60..=3599 => format!("{}分前", secs / 60),
+        3600..=86399 => format!("{}時間前", secs / 3600),
+        _ => format!("{}日前", secs / 86400),
+    }
+}
@@ -0,0 +1,36 @@
+use std::future::Future;
+use tokio::sync::oneshot::error::TryRecvError;
+use tokio::sync::oneshot::Receiver;
+use tokio::task::JoinHandle;
+
+/// request running in background.
+///
+/// the task is aborted when job is dropped.
+/// in-flight http request is dropped with it so engine can stop working on the request.
+pub struct Job<T> {
+    rx: Receiver<T>,
+    handle: JoinHandle<()>,
+}
+
+impl<T: Send + 'static> Job<T> {
+    pub fn spawn<F>(future: F) -> Self
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let handle = tokio::spawn(async move {
+            tx.send(future.await).ok();
+        });
+        Self { rx, handle }
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.rx.try_recv()
+    }
+}
+
+impl<T> Drop for Job<T> {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
@@ -0,0 +1,299 @@
+use crate::commands::{BottomPaneCommand, Phoneme};
+use eframe::egui::{
+    pos2, vec2, Align2, Color32, CursorIcon, DragValue, FontId, Rect, Sense, Stroke, Ui,
+};
+use voice_vox_api::api_schema::AccentPhraseInProject;
+
+/// horizontal scale of timeline.
+const PIXELS_PER_SECOND: f32 = 600.0;
+const BLOCK_HEIGHT: f32 = 40.0;
+const TEXT_HEIGHT: f32 = 20.0;
+/// width of draggable right edge of block.
+const HANDLE_WIDTH: f32 = 6.0;
+/// shorter phoneme breaks synthesis.
+const MIN_LENGTH: f32 = 0.01;
+
+struct Block<'a> {
+    accent_phrase: usize,
+    phoneme: Phoneme,
+    symbol: &'a str,
+    length: f32,
+}
+
+fn blocks(edit_targets: &[AccentPhraseInProject]) -> Vec<Block<'_>> {
+    let mut blocks = Vec::new();
+    for (ap, target) in edit_targets.iter().enumerate() {
+        for (m, mora) in target.moras.iter().enumerate() {
+            if let (Some(consonant), Some(length)) = (&mora.consonant, mora.consonantLength) {
+                blocks.push(Block {
+                    accent_phrase: ap,
+                    phoneme: Phoneme::Consonant(m),
+                    symbol: consonant,
+                    length,
+                });
+            }
+            blocks.push(Block {
+                accent_phrase: ap,
+                phoneme: Phoneme::Vowel(m),
+                symbol: &mora.vowel,
+                length: mora.vowelLength,
+            });
+        }
+        if let Some(pause) = &target.pause_mora {
+            blocks.push(Block {
+                accent_phrase: ap,
+                phoneme: Phoneme::Pause,
+                symbol: &pause.vowel,
+                length: pause.vowelLength,
+            });
+        }
+    }
+    blocks
+}
+
+/// lengths of `blocks[range]` multiplied by `scale`.
+fn scale_lengths(
+    blocks: &[Block],
+    range: std::ops::RangeInclusive<usize>,
+    scale: f32,
+) -> Vec<(usize, Phoneme, f32)> {
+    blocks[range]
+        .iter()
+        .map(|block| {
+            let length = (block.length * scale).max(MIN_LENGTH);
+            (block.accent_phrase, block.phoneme, length - block.length)
+        })
+        .filter(|(_, _, diff)| diff.abs() > f32::EPSILON)
+        .collect()
+}
+
+/// timeline of consonants, vowels and pauses whose right edges are draggable.
+///
+/// pauses are inserted and removed from context menu of blocks.
+pub struct LengthEditor {
+    /// blocks selected for proportional scaling as (anchor, end).
+    selection: Option<(usize, usize)>,
+    scale: f32,
+    /// dragged block and its length while dragging.
+    dragging: Option<(usize, f32)>,
+}
+
+impl Default for LengthEditor {
+    fn default() -> Self {
+        Self {
+            selection: None,
+            scale: 1.0,
+            dragging: None,
+        }
+    }
+}
+
+impl LengthEditor {
+    pub fn ui(
+        &mut self,
+        ui: &mut Ui,
+        edit_targets: &[AccentPhraseInProject],
+    ) -> Option<BottomPaneCommand> {
+        let blocks = blocks(edit_targets);
+        if matches!(self.selection, Some((a, b)) if a.max(b) >= blocks.len()) {
+            self.selection = None;
+        }
+        let selected = self.selection.map(|(a, b)| a.min(b)..=a.max(b));
+        let mut rt = None;
+
+        ui.horizontal(|ui| {
+            ui.label("倍率");
+            ui.add(
+                DragValue::new(&mut self.scale)
+                    .speed(0.01)
+                    .clamp_range(0.25..=4.0),
+            );
+            let apply = ui.add_enabled(
+                selected.is_some(),
+                eframe::egui::Button::new("選択範囲に適用"),
+            );
+            if let (true, Some(range)) = (apply.clicked(), selected.clone()) {
+                let diffs = scale_lengths(&blocks, range, self.scale);
+                if !diffs.is_empty() {
+                    rt = Some(BottomPaneCommand::Lengths { diffs });
+                }
+                self.scale = 1.0;
+            }
+        });
+
+        let total: f32 = blocks.iter().map(|block| block.length).sum();
+        let (rect, _) = ui.allocate_exact_size(
+            vec2(
+                total * PIXELS_PER_SECOND + HANDLE_WIDTH,
+                BLOCK_HEIGHT + TEXT_HEIGHT,
+            ),
+            Sense::hover(),
+        );
+        let painter = ui.painter_at(rect.expand(1.0));
+        let visuals = ui.visuals().clone();
+        let id = ui.id().with("length_editor");
+        let mut left = rect.left();
+        let mut mora_left = left;
+        for (i, block) in blocks.iter().enumerate() {
+            let length = match self.dragging {
+                Some((dragged, length)) if dragged == i => length,
+                _ => block.length,
+            };
+            let right = left + length * PIXELS_PER_SECOND;
+            let block_rect = Rect::from_min_max(
+                pos2(left, rect.top()),
+                pos2(right, rect.top() + BLOCK_HEIGHT),
+            );
+
+            let body = ui.interact(
+                block_rect.shrink2(vec2(HANDLE_WIDTH / 2.0, 0.0)),
+                id.with(("body", i)),
+                Sense::click(),
+            );
+            if body.clicked() {
+                self.selection = match self.selection {
+                    Some((anchor, _)) if ui.input().modifiers.shift => Some((anchor, i)),
+                    _ => Some((i, i)),
+                };
+            }
+            // pause after last accent phrase is meaningless, but existing one can be removed.
+            let accent_phrase = block.accent_phrase;
+            let has_pause = edit_targets[accent_phrase].pause_mora.is_some();
+            if has_pause || accent_phrase + 1 < edit_targets.len() {
+                body.context_menu(|ui| {
+                    if has_pause {
+                        if ui.button("ポーズを削除").clicked() {
+                            rt = Some(BottomPaneCommand::SetPause {
+                                accent_phrase,
+                                pause: None,
+                            });
+                            ui.close_menu();
+                        }
+                    } else if ui.button("ポーズを挿入").clicked() {
+                        rt = Some(BottomPaneCommand::insert_pause(accent_phrase));
+                        ui.close_menu();
+                    }
+                });
+            }
+            let handle = ui
+                .interact(
+                    Rect::from_center_size(
+                        pos2(right, block_rect.center().y),
+                        vec2(HANDLE_WIDTH, BLOCK_HEIGHT),
+                    ),
+                    id.with(("handle", i)),
+                    Sense::drag(),
+                )
+                .on_hover_cursor(CursorIcon::ResizeHorizontal);
+            if handle.dragged() {
+                let input = ui.input();
+                if let (Some(origin), Some(pos)) =
+                    (input.pointer.press_origin(), input.pointer.interact_pos())
+                {
+                    let length = block.length + (pos.x - origin.x) / PIXELS_PER_SECOND;
+                    self.dragging = Some((i, length.max(MIN_LENGTH)));
+                }
+            }
+            if handle.drag_released() {
+                if let Some((_, length)) = self.dragging.take() {
+                    let diff = length - block.length;
+                    if diff.abs() > f32::EPSILON {
+                        rt = Some(match block.phoneme {
+                            Phoneme::Consonant(mora) => BottomPaneCommand::VowelAndConsonant {
+                                accent_phrase,
+                                mora,
+                                vowel_diff: None,
+                                consonant_diff: Some(diff),
+                            },
+                            Phoneme::Vowel(mora) => BottomPaneCommand::VowelAndConsonant {
+                                accent_phrase,
+                                mora,
+                                vowel_diff: Some(diff),
+                                consonant_diff: None,
+                            },
+                            Phoneme::Pause => BottomPaneCommand::Lengths {
+                                diffs: vec![(accent_phrase, Phoneme::Pause, diff)],
+                            },
+                        });
+                    }
+                }
+            }
+
+            let fill = if matches!(&selected, Some(range) if range.contains(&i)) {
+                visuals.selection.bg_fill
+            } else {
+                match block.phoneme {
+                    Phoneme::Consonant(_) => visuals.faint_bg_color,
+                    Phoneme::Vowel(_) => visuals.extreme_bg_color,
+                    Phoneme::Pause => Color32::TRANSPARENT,
+                }
+            };
+            painter.rect(
+                block_rect,
+                0.0,
+                fill,
+                Stroke::new(1.0, visuals.widgets.noninteractive.bg_stroke.color),
+            );
+            painter.text(
+                block_rect.center(),
+                Align2::CENTER_CENTER,
+                block.symbol,
+                FontId::proportional(12.0),
+                visuals.text_color(),
+            );
+            if handle.hovered() || handle.dragged() {
+                painter.vline(right, block_rect.y_range(), visuals.selection.stroke);
+            }
+
+            // mora text spans consonant and vowel.
+            let mora_text = match block.phoneme {
+                Phoneme::Vowel(mora) => Some(&edit_targets[block.accent_phrase].moras[mora].text),
+                Phoneme::Pause => edit_targets[block.accent_phrase]
+                    .pause_mora
+                    .as_ref()
+                    .map(|pause| &pause.text),
+                Phoneme::Consonant(_) => None,
+            };
+            if let Some(text) = mora_text {
+                painter.text(
+                    pos2((mora_left + right) / 2.0, rect.bottom()),
+                    Align2::CENTER_BOTTOM,
+                    text,
+                    FontId::default(),
+                    visuals.text_color(),
+                );
+                mora_left = right;
+            }
+            left = right;
+        }
+        rt
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{blocks, scale_lengths};
+    use crate::commands::Phoneme;
+    use crate::project::test::sample_project;
+    use voice_vox_api::api_schema::MoraInProject;
+
+    #[test]
+    fn scale_selected_span() {
+        let mut query = sample_project().audioItems["line"].query.clone().unwrap();
+        query.accentPhrases[0].pause_mora = Some(MoraInProject {
+            text: "、".to_string(),
+            consonant: None,
+            consonantLength: None,
+            vowel: "pau".to_string(),
+            vowelLength: 0.3,
+            pitch: 0.0,
+        });
+        let blocks = blocks(&query.accentPhrases);
+        assert_eq!(blocks.len(), 2);
+        let diffs = scale_lengths(&blocks, 0..=1, 2.0);
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[1].1, Phoneme::Pause);
+        assert!((diffs[0].2 - 0.1).abs() < 1e-6);
+        assert!((diffs[1].2 - 0.3).abs() < 1e-6);
+    }
+}
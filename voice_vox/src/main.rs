@@ -4,15 +4,22 @@ use eframe::{CreationContext, Frame, NativeOptions};
 use crate::api::Api;
 
 use crate::bottom_pane::Displaying;
+use crate::chara_change_button::CharaChangeCommand;
+use crate::clipboard::Pasted;
 use crate::commands::AudioQueryCommands;
-use crate::dialogue::ExitControl;
-use crate::history::Command;
+use crate::context_menu::CtxMenuOp;
+use crate::dialogue::{ExitControl, SaveOnExit};
+use crate::find_bar::FindOp;
+use crate::history::{Command, EntryId};
+use crate::history_panel::HistoryPanelOp;
+use crate::job::Job;
 use crate::menu::TopMenuOp;
 use crate::project::VoiceVoxProject;
+use crate::synthesis_cache::{SynthesisKey, SynthesisState};
 use crate::tool_bar::ToolBarOp;
+use crate::waveform::Waveform;
 use eframe::egui;
 use std::collections::HashMap;
-use std::io::Cursor;
 use tokio::sync::oneshot::error::TryRecvError;
 use tokio::sync::oneshot::Receiver;
 
@@ -20,20 +27,34 @@ use voice_vox_api::api;
 use voice_vox_api::api_schema;
 mod bottom_pane;
 mod chara_change_button;
+mod clipboard;
 mod commands;
 mod context_menu;
 mod dialogue;
+mod find_bar;
 mod history;
+mod history_panel;
+mod job;
 mod left_pane;
+mod length_editor;
 mod menu;
+mod pitch_editor;
+mod player;
+mod pre_synthesis;
 mod project;
+mod reading;
 mod right_pane;
+mod selection;
+mod synthesis;
+mod synthesis_cache;
 mod tool_bar;
+mod waveform;
 
 enum DialogueKind {
     ExitCustomize,
     RestoreDefault,
     AskDeleteProgress,
+    AskSaveOnExit,
 }
 
 struct VoiceVoxRust {
@@ -45,15 +66,31 @@ struct VoiceVoxRust {
     block_menu_control: bool,
     opening_dialogues: Option<DialogueKind>,
     current_selected_tts_line: String,
+    selection: crate::selection::Selection,
+    clipboard: crate::clipboard::LineClipboard,
+    find_bar: crate::find_bar::FindBar,
     back_up_text: String,
     histories: crate::history::HistoryManager,
     audio_query_jobs: HashMap<String, AudioQueryState>,
+    /// pitch and length recalculation after accent edits.
+    mora_data_jobs: HashMap<String, MoraDataJob>,
     current_displaying: crate::bottom_pane::Displaying,
     /// used to reduce Synthesis request.
-    /// * key : (uuid,timestamp)
-    /// * value : Cursor wrapped wav file.
-    ///
-    synthesis_cache: HashMap<(String, tokio::time::Instant), SynthesisState>,
+    synthesis_cache: crate::synthesis_cache::SynthesisCache,
+    /// background synthesis of lines which are not played yet.
+    pre_synthesis: crate::pre_synthesis::PreSynthesis,
+    player: crate::player::Player,
+    pitch_editor: crate::pitch_editor::PitchEditor,
+    length_editor: crate::length_editor::LengthEditor,
+    reading_editor: crate::reading::ReadingEditor,
+    /// decoded audio of current line.
+    waveform: Option<Waveform>,
+    /// set when user confirmed to close window.
+    exit_confirmed: bool,
+    show_history: bool,
+    /// keep user's pitch deltas from engine values when character is changed.
+    keep_pitch_on_chara_change: bool,
+    export_jobs: Vec<Receiver<Result<(), String>>>,
 }
 
 pub static BLANK_AUDIO_QUERY: once_cell::race::OnceBox<api_schema::AudioQuery> =
@@ -95,11 +132,25 @@ impl VoiceVoxRust {
             block_menu_control: false,
             opening_dialogues: None,
             current_selected_tts_line: String::new(),
+            selection: Default::default(),
+            clipboard: Default::default(),
+            find_bar: Default::default(),
             back_up_text: "".to_string(),
             histories: crate::history::HistoryManager::new(),
             audio_query_jobs: Default::default(),
+            mora_data_jobs: Default::default(),
             current_displaying: Displaying::Accent,
-            synthesis_cache: HashMap::new(),
+            synthesis_cache: Default::default(),
+            pre_synthesis: Default::default(),
+            player: Default::default(),
+            pitch_editor: Default::default(),
+            length_editor: Default::default(),
+            reading_editor: Default::default(),
+            waveform: None,
+            exit_confirmed: false,
+            show_history: false,
+            keep_pitch_on_chara_change: true,
+            export_jobs: Vec::new(),
         }
     }
 
@@ -124,19 +175,717 @@ impl VoiceVoxRust {
 
         cc.egui_ctx.set_fonts(fonts);
     }
+
+    /// write project into `path` and history next to it, then mark current state as saved.
+    fn write_project(&mut self, path: std::path::PathBuf) -> bool {
+        let (json, history) = match self.histories.to_project_file().to_json() {
+            Ok(json) => json,
+            Err(e) => {
+                log::error!("failed to serialize project {}", e);
+                return false;
+            }
+        };
+        if let Err(e) = std::fs::write(&path, json) {
+            log::error!("failed to write project {:?} {}", path, e);
+            return false;
+        }
+        let history_path = project::history_path(&path);
+        let written = match history {
+            Some(history) => std::fs::write(&history_path, history),
+            // history of previous save must not be restored with this project.
+            None => std::fs::remove_file(&history_path).or_else(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    Ok(())
+                } else {
+                    Err(e)
+                }
+            }),
+        };
+        if let Err(e) = written {
+            log::warn!("failed to write history {:?} {}", history_path, e);
+        }
+        self.opening_file = path.to_str().map(|st| st.to_owned());
+        self.histories.save();
+        true
+    }
+
+    /// ask file path and save project.
+    ///
+    /// returns false if canceled or failed.
+    fn save_project_as(&mut self) -> bool {
+        let file = rfd::FileDialog::new()
+            .add_filter("VoiceVox project file", &["vvproj"])
+            .set_directory("/")
+            .save_file();
+        if let Some(path) = file {
+            self.write_project(path)
+        } else {
+            false
+        }
+    }
+
+    /// fold finished mora data into the edit which requested it.
+    fn poll_mora_data_jobs(&mut self) {
+        let lines: Vec<String> = self.mora_data_jobs.keys().cloned().collect();
+        for line in lines {
+            let result = match self
+                .mora_data_jobs
+                .get_mut(&line)
+                .map(|job| job.job.try_recv())
+            {
+                Some(Err(TryRecvError::Empty)) | None => continue,
+                Some(Ok(result)) => result,
+                Some(Err(TryRecvError::Closed)) => Err(api::APIError::Unknown),
+            };
+            let job = self.mora_data_jobs.remove(&line).unwrap();
+            let current = self
+                .histories
+                .project
+                .audioItems
+                .get(&line)
+                .filter(|ai| ai.styleId == job.speaker)
+                .and_then(|ai| ai.query.as_ref())
+                .map(|aq| &aq.accentPhrases);
+            match result {
+                Ok(accent_phrases) if current == Some(&job.sent) => {
+                    let entry = if let Some(entry) = job.entry {
+                        entry
+                    } else {
+                        log::debug!("discard mora data of unrecorded edit for {}", line);
+                        continue;
+                    };
+                    let accent_phrases = accent_phrases
+                        .into_iter()
+                        .skip(job.affected.start)
+                        .take(job.affected.len())
+                        .map(|ap| ap.into())
+                        .collect();
+                    self.histories.amend(
+                        Box::new(AudioQueryCommands::UpdateMoraData {
+                            start: job.affected.start,
+                            accent_phrases,
+                        }),
+                        line,
+                        entry,
+                    );
+                }
+                Ok(_) => log::debug!("discard stale mora data for {}", line),
+                Err(e) => log::error!("mora data request for {} failed {:?}", line, e),
+            }
+        }
+    }
+
+    /// let engine requests for `line` sent along with an edit fold their results into `entry`.
+    fn bind_jobs(&mut self, line: &str, entry: EntryId) {
+        if let Some(AudioQueryState::WaitingForQuery(request, _)) =
+            self.audio_query_jobs.get_mut(line)
+        {
+            if request.amend && request.entry.is_none() {
+                request.entry = Some(entry);
+            }
+        }
+        if let Some(job) = self.mora_data_jobs.get_mut(line) {
+            if job.entry.is_none() {
+                job.entry = Some(entry);
+            }
+        }
+    }
+
+    /// insert each line of text file after current line.
+    ///
+    /// recorded as single history entry.
+    fn load_text(&mut self) {
+        let file = rfd::FileDialog::new()
+            .add_filter("Text file", &["txt"])
+            .set_directory("/")
+            .pick_file();
+        let text = if let Some(text) = file.and_then(|path| std::fs::read_to_string(path).ok()) {
+            text
+        } else {
+            return;
+        };
+        let style_id = self
+            .histories
+            .project
+            .audioItems
+            .get(&self.current_selected_tts_line)
+            .map(|ai| ai.styleId)
+            .unwrap_or(2);
+        let lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned)
+            .collect();
+        let index = self.index_after(&self.current_selected_tts_line);
+        self.histories.begin_transaction("テキスト読み込み");
+        self.insert_text_lines(lines, style_id, index);
+        self.histories.commit_transaction();
+    }
+
+    /// index of `audioKeys` next to `line`. end of project if `line` is not found.
+    fn index_after(&self, line: &str) -> usize {
+        let order = &self.histories.project.audioKeys;
+        order
+            .iter()
+            .position(|x| x == line)
+            .map_or(order.len(), |index| index + 1)
+    }
+
+    /// insert each of `lines` from `index` as new line of `style_id` and fetch its query.
+    fn insert_text_lines(&mut self, lines: Vec<String>, style_id: i32, index: usize) {
+        for (index, line) in (index..).zip(lines) {
+            let uuid = uuid::Uuid::new_v4().to_string();
+            log::debug!("new uuid {}", uuid);
+            let entry = self.histories.invoke(
+                Box::new(AudioQueryCommands::Insert(
+                    index,
+                    crate::project::AudioItem {
+                        text: line.clone(),
+                        styleId: style_id,
+                        query: Some(BLANK_AUDIO_QUERY.get().cloned().unwrap().into()),
+                        presetKey: None,
+                        morphingInfo: None,
+                        interrogativeUpspeak: None,
+                    },
+                )),
+                uuid.clone(),
+            );
+            self.audio_query_jobs.insert(
+                uuid,
+                spawn_audio_query(
+                    AudioQueryRequest {
+                        text: line.clone(),
+                        prev_text: line,
+                        amend: true,
+                        entry: Some(entry),
+                        base: None,
+                    },
+                    style_id,
+                ),
+            );
+        }
+    }
+
+    /// cut, copy and paste lines.
+    ///
+    /// selected lines are target if `clicked` line is selected.
+    fn apply_context_menu_op(&mut self, op: CtxMenuOp, clicked: &str) {
+        let order = &self.histories.project.audioKeys;
+        let targets = self.selection.targets(clicked, order);
+        let items = &self.histories.project.audioItems;
+        match op {
+            CtxMenuOp::Copy | CtxMenuOp::Cut => {
+                self.clipboard.copy(
+                    targets
+                        .iter()
+                        .filter_map(|line| items.get(line).cloned())
+                        .collect(),
+                );
+                // cut is disabled while it would remove every line.
+                if matches!(op, CtxMenuOp::Cut) && targets.len() < order.len() {
+                    self.histories.begin_transaction("切り取り");
+                    for line in targets {
+                        self.histories
+                            .invoke(Box::new(AudioQueryCommands::Remove(0, None)), line);
+                    }
+                    self.histories.commit_transaction();
+                }
+            }
+            CtxMenuOp::Paste => {
+                let style_id = items.get(clicked).map(|ai| ai.styleId).unwrap_or(2);
+                let index = self.index_after(clicked);
+                match self.clipboard.paste() {
+                    Some(Pasted::Lines(items)) => {
+                        self.histories.begin_transaction("貼り付け");
+                        for (index, item) in (index..).zip(items) {
+                            let uuid = uuid::Uuid::new_v4().to_string();
+                            log::debug!("new uuid {}", uuid);
+                            self.histories
+                                .invoke(Box::new(AudioQueryCommands::Insert(index, item)), uuid);
+                        }
+                        self.histories.commit_transaction();
+                    }
+                    Some(Pasted::Text(lines)) => {
+                        self.histories.begin_transaction("貼り付け");
+                        self.insert_text_lines(lines, style_id, index);
+                        self.histories.commit_transaction();
+                    }
+                    None => {}
+                }
+            }
+            CtxMenuOp::PasteTuning => {
+                let pastes: Vec<_> = targets
+                    .into_iter()
+                    .filter_map(|line| {
+                        let copied = self.clipboard.tuning_for(&items.get(&line)?.text)?;
+                        Some((copied.query.clone(), line))
+                    })
+                    .collect();
+                self.histories.begin_transaction("調整の貼り付け");
+                for (query, line) in pastes {
+                    self.histories
+                        .invoke(Box::new(AudioQueryCommands::ReplaceQuery(query)), line);
+                }
+                self.histories.commit_transaction();
+            }
+            CtxMenuOp::SelectAll => self.selection.select_all(order),
+        }
+    }
+
+    /// set text of lines as single history entry and fetch their queries again.
+    fn replace_texts(&mut self, changes: Vec<(String, String)>) {
+        self.histories.begin_transaction("すべて置換");
+        for (line, text) in changes {
+            let style_id = match self.histories.project.audioItems.get(&line) {
+                Some(ai) => ai.styleId,
+                None => continue,
+            };
+            match replace_text(&mut self.histories, &line, text) {
+                Some(request) => {
+                    self.audio_query_jobs
+                        .insert(line, spawn_audio_query(request, style_id));
+                }
+                None => {
+                    self.audio_query_jobs.remove(&line);
+                }
+            }
+        }
+        self.histories.commit_transaction();
+    }
+
+    /// abort queries for text which undo or redo replaced.
+    ///
+    /// result of such query would put accent phrases of other text into line.
+    fn cancel_outdated_queries(&mut self) {
+        let items = &self.histories.project.audioItems;
+        self.audio_query_jobs.retain(|line, state| {
+            let current = matches!(items.get(line), Some(ai) if ai.text == state.request().text);
+            if !current {
+                log::debug!("cancel audio query of outdated text for {}", line);
+            }
+            current
+        });
+    }
+
+    /// write current line into wav file chosen by user.
+    fn export_selected(&mut self) {
+        let ai = match self
+            .histories
+            .project
+            .audioItems
+            .get(&self.current_selected_tts_line)
+        {
+            Some(ai) if ai.query.is_some() => ai.clone(),
+            _ => return,
+        };
+        let path = if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Wave file", &["wav"])
+            .set_file_name(&format!("{}.wav", file_name_of(&ai.text)))
+            .save_file()
+        {
+            path
+        } else {
+            return;
+        };
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let result = match crate::synthesis::synthesize(ai).await {
+                Ok(wav) => std::fs::write(&path, wav).map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            tx.send(result).ok();
+        });
+        self.export_jobs.push(rx);
+    }
+
+    /// write every line into wav files in folder chosen by user.
+    fn export_all(&mut self) {
+        let project = &self.histories.project;
+        let items: Vec<_> = project
+            .audioKeys
+            .iter()
+            .filter_map(|line| project.audioItems.get(line))
+            .filter(|ai| ai.query.is_some())
+            .cloned()
+            .collect();
+        if items.is_empty() {
+            return;
+        }
+        let dir = if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+            dir
+        } else {
+            return;
+        };
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let names: Vec<_> = items
+                .iter()
+                .enumerate()
+                .map(|(i, ai)| format!("{:03}_{}.wav", i + 1, file_name_of(&ai.text)))
+                .collect();
+            let result = match crate::synthesis::synthesize_all(items).await {
+                Ok(waves) => names
+                    .iter()
+                    .zip(waves)
+                    .try_for_each(|(name, wav)| std::fs::write(dir.join(name), wav))
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            tx.send(result).ok();
+        });
+        self.export_jobs.push(rx);
+    }
+
+    /// abort synthesis in flight.
+    fn cancel_synthesis(&mut self) {
+        self.synthesis_cache.cancel_waiting();
+    }
+
+    /// abort every request of closed project.
+    fn cancel_all_jobs(&mut self) {
+        self.audio_query_jobs.clear();
+        self.mora_data_jobs.clear();
+        self.synthesis_cache.clear();
+    }
+
+    /// abort requests whose result would be discarded.
+    ///
+    /// synthesis of outdated revision and requests for removed lines are aborted.
+    fn cancel_stale_jobs(&mut self) {
+        let items = &self.histories.project.audioItems;
+        let current = items
+            .iter()
+            .filter_map(|(line, ai)| Some((line.as_str(), SynthesisKey::of(ai)?)))
+            .collect();
+        self.synthesis_cache.retain_lines(&current);
+        self.audio_query_jobs
+            .retain(|line, _| items.contains_key(line));
+        self.mora_data_jobs
+            .retain(|line, _| items.contains_key(line));
+    }
+
+    /// report failed exports.
+    fn poll_export_jobs(&mut self) {
+        let mut pending = Vec::new();
+        for mut rx in self.export_jobs.drain(..) {
+            let result = match rx.try_recv() {
+                Err(TryRecvError::Empty) => {
+                    pending.push(rx);
+                    continue;
+                }
+                Ok(result) => result,
+                Err(TryRecvError::Closed) => Err(api::APIError::Unknown.to_string()),
+            };
+            if let Err(reason) = result {
+                log::error!("export failed {}", reason);
+                rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Error)
+                    .set_title("書き出しに失敗しました")
+                    .set_description(&reason)
+                    .show();
+            }
+        }
+        self.export_jobs = pending;
+    }
+
+    /// open project file. failure is reported in dialog and current project is kept.
+    fn load_project(&mut self, path: std::path::PathBuf) {
+        // missing history is not an error. the project may come from official editor.
+        let history = std::fs::read_to_string(project::history_path(&path)).ok();
+        let vvproj = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                project::VoiceVoxProjectFile::from_json(&json, history.as_deref())
+                    .map_err(|e| e.to_string())
+            });
+        match vvproj {
+            Ok(vvproj) => {
+                self.opening_file = path.to_str().map(|st| st.to_owned());
+                self.histories = history::HistoryManager::from_project_file(vvproj);
+                self.cancel_all_jobs();
+            }
+            Err(reason) => {
+                log::error!("failed to load project {:?} {}", path, reason);
+                rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Error)
+                    .set_title("プロジェクトを読み込めませんでした")
+                    .set_description(&reason)
+                    .show();
+            }
+        }
+    }
+
+    /// overwrite opening project file.
+    ///
+    /// if project has never been saved, ask file path.
+    fn overwrite_project(&mut self) -> bool {
+        if let Some(path) = self.opening_file.clone() {
+            self.write_project(path.into())
+        } else {
+            self.save_project_as()
+        }
+    }
 }
 
 enum AudioQueryState {
-    WaitingForQuery(String, Receiver<<crate::api::AudioQuery as Api>::Response>),
-    NoJob,
-    Finished(String, api_schema::AudioQuery),
-    Failed,
+    WaitingForQuery(
+        AudioQueryRequest,
+        Job<<crate::api::AudioQuery as Api>::Response>,
+    ),
+    Finished(AudioQueryRequest, api_schema::AudioQuery),
+    /// request is kept for retry with readable reason of failure.
+    Failed(AudioQueryRequest, String),
+}
+
+impl AudioQueryState {
+    fn request(&self) -> &AudioQueryRequest {
+        match self {
+            AudioQueryState::WaitingForQuery(request, _)
+            | AudioQueryState::Finished(request, _)
+            | AudioQueryState::Failed(request, _) => request,
+        }
+    }
+
+    /// receive result of request in flight.
+    fn poll(&mut self) {
+        if let AudioQueryState::WaitingForQuery(request, job) = self {
+            let result = match job.try_recv() {
+                Err(TryRecvError::Empty) => return,
+                Ok(result) => result,
+                Err(TryRecvError::Closed) => Err(api::APIError::Unknown),
+            };
+            let request = std::mem::replace(
+                request,
+                AudioQueryRequest {
+                    text: String::new(),
+                    prev_text: String::new(),
+                    amend: false,
+                    entry: None,
+                    base: None,
+                },
+            );
+            *self = match result {
+                Ok(aq) => AudioQueryState::Finished(request, aq),
+                Err(e) => {
+                    log::error!("audio query for {} failed {:?}", request.text, e);
+                    AudioQueryState::Failed(request, e.to_string())
+                }
+            };
+        }
+    }
+}
+
+struct AudioQueryRequest {
+    text: String,
+    /// text of line before the edit. restored on undo.
+    prev_text: String,
+    /// fold result into the edit which requested it instead of new history entry.
+    amend: bool,
+    /// history entry of the edit. set when the edit is recorded.
+    entry: Option<EntryId>,
+    /// accent phrases to carry tuning over from. `None` uses current accent phrases of line.
+    base: Option<Vec<api_schema::AccentPhraseInProject>>,
+}
+
+/// send audio query request in background.
+fn spawn_audio_query(request: AudioQueryRequest, speaker: i32) -> AudioQueryState {
+    let query = api::AudioQuery {
+        text: request.text.clone(),
+        speaker,
+        core_version: None,
+    };
+    AudioQueryState::WaitingForQuery(request, Job::spawn(async move { query.call().await }))
+}
+
+/// set text of `line` to `text` and return request to fetch its accent phrases.
+///
+/// accent phrases are cleared until the result arrives so that audio of old text is not played
+/// if the request fails or is cancelled by undo. `None` if `text` is empty.
+fn replace_text(
+    histories: &mut history::HistoryManager,
+    line: &str,
+    text: String,
+) -> Option<AudioQueryRequest> {
+    let ai = histories.project.audioItems.get(line)?;
+    let base = ai.query.as_ref().map(|aq| aq.accentPhrases.clone());
+    let cmd = AudioQueryCommands::UpdateAccentPhrases {
+        new_text: text.clone(),
+        prev_text: ai.text.clone(),
+        accent_phrases: vec![],
+    };
+    let entry = histories.invoke(Box::new(cmd), line.to_owned());
+    if text.is_empty() {
+        None
+    } else {
+        Some(AudioQueryRequest {
+            text: text.clone(),
+            prev_text: text,
+            amend: true,
+            entry: Some(entry),
+            base,
+        })
+    }
+}
+
+struct MoraDataJob {
+    /// accent phrases sent to engine. result is discarded if line is edited meanwhile.
+    sent: Vec<api_schema::AccentPhraseInProject>,
+    speaker: i32,
+    /// accent phrases to take from result.
+    affected: std::ops::Range<usize>,
+    /// history entry of the edit which result is folded into. set when the edit is recorded.
+    entry: Option<EntryId>,
+    job: Job<<crate::api::MoraData as Api>::Response>,
+}
+
+/// request pitch and length of `accent_phrases` in background.
+///
+/// if `baseline` style is given, user's pitch deltas from the engine values of `baseline` are kept.
+fn spawn_mora_data(
+    accent_phrases: Vec<api_schema::AccentPhraseInProject>,
+    speaker: i32,
+    affected: std::ops::Range<usize>,
+    baseline: Option<i32>,
+) -> MoraDataJob {
+    let request = api::MoraData {
+        speaker,
+        core_version: None,
+        accent_phrases: accent_phrases.iter().map(|ap| ap.clone().into()).collect(),
+    };
+    let current = accent_phrases.clone();
+    let job = Job::spawn(async move {
+        match (request.call().await, baseline) {
+            (Ok(mut target), Some(baseline)) => api::MoraData {
+                speaker: baseline,
+                core_version: None,
+                accent_phrases: request.accent_phrases,
+            }
+            .call()
+            .await
+            .map(|baseline| {
+                keep_pitch_delta(&current, &baseline, &mut target);
+                target
+            }),
+            (result, _) => result,
+        }
+    });
+    MoraDataJob {
+        sent: accent_phrases,
+        speaker,
+        affected,
+        entry: None,
+        job,
+    }
+}
+
+/// max chars of line text used in exported file name.
+const FILE_NAME_LEN: usize = 40;
+
+/// line text usable as file name on any platform.
+///
+/// path separators, reserved and control chars are replaced with `_` and long text is truncated.
+fn file_name_of(text: &str) -> String {
+    let name: String = text
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '?' | '*' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .take(FILE_NAME_LEN)
+        .collect();
+    // windows drops trailing dots and spaces.
+    let name = name.trim_end_matches(['.', ' ']);
+    if name.is_empty() {
+        "_".to_owned()
+    } else {
+        name.to_owned()
+    }
+}
+
+/// shift pitch of `target` by the difference between `current` and `baseline`.
+///
+/// unvoiced moras stay unvoiced.
+fn keep_pitch_delta(
+    current: &[api_schema::AccentPhraseInProject],
+    baseline: &[api_schema::AccentPhrase],
+    target: &mut [api_schema::AccentPhrase],
+) {
+    let current = current.iter().flat_map(|ap| ap.moras.iter());
+    let baseline = baseline.iter().flat_map(|ap| ap.moras.iter());
+    let target = target.iter_mut().flat_map(|ap| ap.moras.iter_mut());
+    for (mora, (current, baseline)) in target.zip(current.zip(baseline)) {
+        if mora.pitch > 0.0 && current.pitch > 0.0 && baseline.pitch > 0.0 {
+            mora.pitch = (mora.pitch + current.pitch - baseline.pitch).max(0.0);
+        }
+    }
 }
 
-enum SynthesisState {
-    WaitingForSynthesis(Receiver<<crate::api::Synthesis as Api>::Response>),
-    Finished(Cursor<Vec<u8>>),
-    Failed,
+/// re-fetch speaker dependent values of `audio_item` for `style_id`.
+///
+/// accent structure and lengths are taken from the engine unless the query is not fetched yet.
+fn spawn_chara_change_job(
+    line: &str,
+    audio_item: &project::AudioItem,
+    style_id: i32,
+    keep_pitch: bool,
+    audio_query_jobs: &mut HashMap<String, AudioQueryState>,
+    mora_data_jobs: &mut HashMap<String, MoraDataJob>,
+) {
+    // pending text edit is sent again with new style.
+    let pending = match audio_query_jobs.get(line) {
+        Some(AudioQueryState::WaitingForQuery(request, _)) => Some((
+            request.prev_text.clone(),
+            request.amend,
+            request.entry,
+            request.base.clone(),
+        )),
+        _ => None,
+    };
+    match &audio_item.query {
+        Some(aq) if pending.is_none() => {
+            log::debug!("send mora data request for {} with id {}.", line, style_id);
+            mora_data_jobs.insert(
+                line.to_owned(),
+                spawn_mora_data(
+                    aq.accentPhrases.clone(),
+                    style_id,
+                    0..aq.accentPhrases.len(),
+                    if keep_pitch {
+                        Some(audio_item.styleId)
+                    } else {
+                        None
+                    },
+                ),
+            );
+        }
+        _ if !audio_item.text.is_empty() => {
+            log::debug!(
+                "send audio query request for {} with id {}.",
+                line,
+                style_id
+            );
+            let (prev_text, amend, entry, base) =
+                pending.unwrap_or_else(|| (audio_item.text.clone(), true, None, None));
+            audio_query_jobs.insert(
+                line.to_owned(),
+                spawn_audio_query(
+                    AudioQueryRequest {
+                        text: audio_item.text.clone(),
+                        prev_text,
+                        amend,
+                        entry,
+                        base,
+                    },
+                    style_id,
+                ),
+            );
+        }
+        _ => {}
+    }
 }
 
 impl eframe::App for VoiceVoxRust {
@@ -149,8 +898,10 @@ impl eframe::App for VoiceVoxRust {
 
         let menu_bar_op = egui::containers::TopBottomPanel::top("TopMenu")
             .show(ctx, |ui| {
-                ui.add_enabled_ui(!self.block_menu_control, crate::menu::create_menu_bar)
-                    .inner
+                ui.add_enabled_ui(!self.block_menu_control, |ui| {
+                    crate::menu::create_menu_bar(ui, &mut self.keep_pitch_on_chara_change)
+                })
+                .inner
             })
             .inner;
 
@@ -161,31 +912,23 @@ impl eframe::App for VoiceVoxRust {
                         log::trace!("opening new project dialogue");
                         self.opening_dialogues = Some(DialogueKind::AskDeleteProgress);
                         self.block_menu_control = true;
+                    } else {
+                        self.histories = history::HistoryManager::new();
+                        self.cancel_all_jobs();
+                        self.opening_file = None;
                     }
                 }
-                TopMenuOp::AudioOutput => {}
-                TopMenuOp::OutputOne => {}
+                TopMenuOp::AudioOutput => self.export_all(),
+                TopMenuOp::OutputOne => self.export_selected(),
                 TopMenuOp::OutputConnected => {}
-                TopMenuOp::LoadText => {}
+                TopMenuOp::LoadText => {
+                    self.load_text();
+                }
                 TopMenuOp::OverwriteProject => {
-                    self.histories.save();
+                    self.overwrite_project();
                 }
                 TopMenuOp::SaveProjectAs => {
-                    let file = rfd::FileDialog::new()
-                        .add_filter("VoiceVox project file", &["vvproj"])
-                        .set_directory("/")
-                        .save_file();
-                    if let Some(path) = file {
-                        std::fs::write(
-                            path.clone(),
-                            serde_json::to_string(&self.histories.project).unwrap(),
-                        )
-                        .unwrap();
-                        let path = path.to_str().map(|st| st.to_owned());
-
-                        self.opening_file = path;
-                        self.histories.save();
-                    }
+                    self.save_project_as();
                 }
                 TopMenuOp::LoadProject => {
                     let file = rfd::FileDialog::new()
@@ -193,13 +936,23 @@ impl eframe::App for VoiceVoxRust {
                         .set_directory("/")
                         .pick_file();
                     if let Some(path) = file {
-                        if let Ok(json) = std::fs::read_to_string(path.clone()) {
-                            let vvproj = serde_json::from_str(&json).unwrap();
-                            self.opening_file = path.to_str().map(|st| st.to_owned());
-                            self.histories = history::HistoryManager::from_project(vvproj);
-                        }
+                        self.load_project(path);
                     }
                 }
+                TopMenuOp::Undo => {
+                    self.histories.undo();
+                    self.cancel_outdated_queries();
+                }
+                TopMenuOp::Redo => {
+                    self.histories.redo();
+                    self.cancel_outdated_queries();
+                }
+                TopMenuOp::Find => {
+                    self.find_bar.open = true;
+                }
+                TopMenuOp::History => {
+                    self.show_history = !self.show_history;
+                }
                 TopMenuOp::RebootEngine => {}
                 TopMenuOp::KeyConfig => {}
                 TopMenuOp::ToolBarCustomize => {
@@ -218,6 +971,14 @@ impl eframe::App for VoiceVoxRust {
         match self.current_view {
             CurrentView::Main => {
                 let mut invocations: Vec<(Box<dyn Command>, String)> = vec![];
+                // set when invocations are bulk operation over selected lines.
+                let mut transaction_name = None;
+                let mut mora_data_request = None;
+                // engine results completing earlier edits.
+                let mut amendments: Vec<(Box<dyn Command>, String, EntryId)> = vec![];
+                // operation chosen from context menu of line.
+                let mut ctx_menu_op = None;
+                let mut replacements = None;
 
                 egui::containers::TopBottomPanel::bottom("voice_control").show(ctx, |ui| {
                     if let Some(ai) = self
@@ -228,53 +989,57 @@ impl eframe::App for VoiceVoxRust {
                     {
                         let mut should_play = None;
                         if let Some(query) = &ai.query {
+                            let key = SynthesisKey::of(ai);
+                            // decode again only when audio of line changed.
+                            if self.waveform.as_ref().map(|waveform| waveform.key) != key {
+                                self.waveform =
+                                    key.and_then(|key| match self.synthesis_cache.peek(&key) {
+                                        Some(SynthesisState::Finished(wav)) => {
+                                            Waveform::new(key, wav.get_ref(), query)
+                                        }
+                                        _ => None,
+                                    });
+                            }
+                            if let Some(cmd) = self.reading_editor.ui(
+                                ui,
+                                &self.current_selected_tts_line,
+                                ai,
+                                &query.accentPhrases,
+                            ) {
+                                invocations
+                                    .push((Box::new(cmd), self.current_selected_tts_line.clone()));
+                            }
                             if let Some(cmd) = crate::bottom_pane::create_bottom_pane(
                                 &mut self.current_displaying,
+                                &mut self.pitch_editor,
+                                &mut self.length_editor,
                                 &mut should_play,
                                 ui,
                                 &query.accentPhrases,
+                                self.player.is_active(),
+                                self.waveform.as_ref(),
+                                key.and_then(|key| self.player.position(&key)),
                             ) {
+                                if let Some(affected) = cmd.affected_accent_phrases() {
+                                    mora_data_request =
+                                        Some((self.current_selected_tts_line.clone(), affected));
+                                }
                                 invocations
                                     .push((Box::new(cmd), self.current_selected_tts_line.clone()));
                             }
-                            if let Some(true) = should_play {
-                                if let Some(instant) = self
-                                    .histories
-                                    .get_current_time_stamp(&self.current_selected_tts_line)
-                                {
-                                    let key = (self.current_selected_tts_line.clone(), instant);
-                                    if let None = self.synthesis_cache.get(&key) {
-                                        let (tx, rx) = tokio::sync::oneshot::channel();
-                                        log::debug!(
-                                            "send synthesis request for {} @ {:?}",
-                                            self.current_selected_tts_line,
-                                            instant
-                                        );
-                                        self.synthesis_cache
-                                            .insert(key, SynthesisState::WaitingForSynthesis(rx));
-                                        if let Some(ai) = self
-                                            .histories
-                                            .project
-                                            .audioItems
-                                            .get(&self.current_selected_tts_line)
-                                        {
-                                            let ai = ai.clone();
-                                            tokio::spawn(async move {
-                                                tx.send(
-                                                    api::Synthesis {
-                                                        speaker: ai.styleId,
-                                                        enable_interrogative_upspeak: None,
-                                                        core_version: None,
-                                                        audio_query: ai.query.unwrap().into(),
-                                                    }
-                                                    .call()
-                                                    .await,
-                                                )
-                                                .unwrap()
-                                            });
-                                        }
-                                    }
+                            match (should_play, key) {
+                                (Some(true), Some(key)) => {
+                                    self.synthesis_cache
+                                        .request(&self.current_selected_tts_line, ai);
+                                    self.player.queue(key);
                                 }
+                                (Some(false), _) => self.player.stop(),
+                                _ => {}
+                            }
+                            if let Some(SynthesisState::Failed(reason)) =
+                                SynthesisKey::of(ai).and_then(|key| self.synthesis_cache.get(&key))
+                            {
+                                ui.colored_label(Color32::RED, reason);
                             }
                         }
                     }
@@ -286,18 +1051,27 @@ impl eframe::App for VoiceVoxRust {
                         {
                             match toolbar_op {
                                 ToolBarOp::PlayAll => {}
-                                ToolBarOp::Stop => {}
-                                ToolBarOp::ExportSelected => {}
-                                ToolBarOp::ExportAll => {}
+                                ToolBarOp::Stop => {
+                                    self.player.stop();
+                                    self.cancel_synthesis();
+                                }
+                                ToolBarOp::ExportSelected => self.export_selected(),
+                                ToolBarOp::ExportAll => self.export_all(),
                                 ToolBarOp::ExportAllInOneFile => {}
-                                ToolBarOp::SaveProject => {}
+                                ToolBarOp::SaveProject => {
+                                    self.overwrite_project();
+                                }
                                 ToolBarOp::Undo => {
                                     self.histories.undo();
+                                    self.cancel_outdated_queries();
                                 }
                                 ToolBarOp::Redo => {
                                     self.histories.redo();
+                                    self.cancel_outdated_queries();
+                                }
+                                ToolBarOp::LoadText => {
+                                    self.load_text();
                                 }
-                                ToolBarOp::LoadText => {}
                                 ToolBarOp::Blank => {}
                             }
                         }
@@ -327,21 +1101,46 @@ impl eframe::App for VoiceVoxRust {
                         egui::containers::SidePanel::right("parameter_control").show_inside(
                             ui,
                             |ui| {
-                                if let Some(audio_item) = self
-                                    .histories
-                                    .project
-                                    .audioItems
-                                    .get(&self.current_selected_tts_line)
+                                let project = &self.histories.project;
+                                let targets: Vec<_> = self
+                                    .selection
+                                    .ordered(&project.audioKeys)
+                                    .into_iter()
+                                    .filter_map(|line| {
+                                        let aq = project.audioItems.get(&line)?.query.as_ref()?;
+                                        Some((line, aq))
+                                    })
+                                    .collect();
+                                let queries: Vec<_> = targets.iter().map(|(_, aq)| *aq).collect();
+                                if let Some(x) =
+                                    crate::right_pane::render_synthesis_control(&queries, ui)
                                 {
-                                    if let Some(aq) = &audio_item.query {
-                                        if let Some(x) =
-                                            crate::right_pane::render_synthesis_control(aq, ui)
-                                        {
-                                            invocations.push((
-                                                Box::new(x),
-                                                self.current_selected_tts_line.clone(),
-                                            ));
-                                        }
+                                    for (line, aq) in &targets {
+                                        invocations.push((Box::new(x.diff_from(aq)), line.clone()));
+                                    }
+                                    if targets.len() > 1 {
+                                        transaction_name = Some("パラメータ一括変更");
+                                    }
+                                }
+                                if let Some(ai) =
+                                    project.audioItems.get(&self.current_selected_tts_line)
+                                {
+                                    ui.separator();
+                                    if let Some(cmd) =
+                                        crate::right_pane::render_morphing_control(ai, ui)
+                                    {
+                                        invocations.push((
+                                            Box::new(cmd),
+                                            self.current_selected_tts_line.clone(),
+                                        ));
+                                    }
+                                    if let Some(cmd) =
+                                        crate::right_pane::render_upspeak_control(ai, ui)
+                                    {
+                                        invocations.push((
+                                            Box::new(cmd),
+                                            self.current_selected_tts_line.clone(),
+                                        ));
                                     }
                                 }
                             },
@@ -350,14 +1149,45 @@ impl eframe::App for VoiceVoxRust {
                             let bottom_right = ui.max_rect().max;
                             let available_with = ui.available_width() - 64.0;
 
+                            // chara change or deletion over multiple selected lines.
+                            let mut bulk_chara_change = None;
+                            let mut bulk_remove = false;
+                            // line dragged by its handle and whether it is dropped in this frame.
+                            let mut dragging = None;
+                            let mut row_rects = vec![];
+                            // hit of find bar to bring into view.
+                            let mut scroll_to = None;
+                            if self.find_bar.open {
+                                match self.find_bar.ui(ui, &self.histories.project) {
+                                    Some(FindOp::Jump(line)) => {
+                                        self.selection.select(&line);
+                                        self.current_selected_tts_line = line.clone();
+                                        scroll_to = Some(line);
+                                    }
+                                    Some(FindOp::ReplaceAll(changes)) => replacements = Some(changes),
+                                    None => {}
+                                }
+                                ui.separator();
+                            }
+                            let find_pattern = self.find_bar.pattern();
                             egui::ScrollArea::vertical().show(ui, |ui| {
                                 ui.set_min_width(available_with);
                                 let len = self.histories.project.audioItems.len();
-                                for line in self.histories.project.audioKeys.iter() {
+                                let order = &self.histories.project.audioKeys;
+                                for line in order.iter() {
                                     let tts_line =
                                         self.histories.project.audioItems.get_mut(line).unwrap();
+                                    let selected = self.selection.contains(line);
+                                    let bulk = selected && self.selection.len() > 1;
+                                    let background = ui.painter().add(egui::Shape::Noop);
 
-                                    ui.horizontal(|ui| {
+                                    let row = ui.horizontal(|ui| {
+                                        let handle = ui
+                                            .add(egui::Label::new("≡").sense(egui::Sense::drag()))
+                                            .on_hover_cursor(egui::CursorIcon::Grab);
+                                        if handle.dragged() || handle.drag_released() {
+                                            dragging = Some((line.clone(), handle.drag_released()));
+                                        }
                                         let ccb = chara_change_button::CharaChangeButton(
                                             tts_line.styleId,
                                         );
@@ -375,114 +1205,280 @@ impl eframe::App for VoiceVoxRust {
                                             self.back_up_text = tts_line.text.clone();
                                         }
 
+                                        if res.clicked() {
+                                            let modifiers = ui.input().modifiers;
+                                            self.selection.click(line, order, modifiers);
+                                        } else if res.gained_focus() {
+                                            self.selection.select(line);
+                                        }
+
                                         if res.has_focus() {
                                             self.current_selected_tts_line = line.clone();
                                         }
+                                        // failure of previous text is no longer relevant.
+                                        if res.changed()
+                                            && matches!(
+                                                self.audio_query_jobs.get(line),
+                                                Some(AudioQueryState::Failed(..))
+                                            )
+                                        {
+                                            self.audio_query_jobs.remove(line);
+                                        }
                                         //フォーカスを失ったら合成リクエストを送る.
-                                        if res.lost_focus() && !tts_line.text.is_empty() {
+                                        let unchanged = tts_line.text == self.back_up_text
+                                            && matches!(&tts_line.query, Some(aq) if !aq.accentPhrases.is_empty());
+                                        if res.lost_focus()
+                                            && !tts_line.text.is_empty()
+                                            && !unchanged
+                                        {
                                             log::debug!("send audio query request for {}", line);
-                                            let speaker = tts_line.styleId;
-                                            let (tx, rx) = tokio::sync::oneshot::channel();
-                                            let text = tts_line.text.clone();
                                             self.audio_query_jobs.insert(
                                                 line.clone(),
-                                                AudioQueryState::WaitingForQuery(text.clone(), rx),
+                                                spawn_audio_query(
+                                                    AudioQueryRequest {
+                                                        text: tts_line.text.clone(),
+                                                        prev_text: self.back_up_text.clone(),
+                                                        amend: false,
+                                                        entry: None,
+                                                        base: None,
+                                                    },
+                                                    tts_line.styleId,
+                                                ),
                                             );
-                                            tokio::spawn(async move {
-                                                tx.send(
-                                                    api::AudioQuery {
-                                                        text: text.clone(),
-                                                        speaker,
-                                                        core_version: None,
-                                                    }
-                                                    .call()
-                                                    .await,
-                                                )
-                                                .unwrap();
-                                            });
                                         }
-                                        if len > 1 {
-                                            if ui.button("X").clicked() {
+                                        let can_paste_tuning =
+                                            self.clipboard.tuning_for(&tts_line.text).is_some();
+                                        // cut and deletion keep at least one line.
+                                        let removable = self.selection.can_remove(line, order);
+                                        res.context_menu(|ui| {
+                                            if let Some(op) = crate::context_menu::create_context_menu(
+                                                ui,
+                                                removable,
+                                                can_paste_tuning,
+                                            ) {
+                                                ctx_menu_op = Some((op, line.clone()));
+                                                ui.close_menu();
+                                            }
+                                        });
+                                        match SynthesisKey::of(tts_line)
+                                            .and_then(|key| self.synthesis_cache.peek(&key))
+                                        {
+                                            Some(SynthesisState::Finished(_)) => {
+                                                ui.colored_label(Color32::GREEN, "●")
+                                                    .on_hover_text("再生準備完了");
+                                            }
+                                            Some(SynthesisState::WaitingForSynthesis(_)) => {
+                                                ui.spinner();
+                                            }
+                                            Some(SynthesisState::Failed(reason)) => {
+                                                ui.colored_label(Color32::RED, "⚠")
+                                                    .on_hover_text(reason);
+                                                if ui.button("再試行").clicked() {
+                                                    self.synthesis_cache.request(line, tts_line);
+                                                }
+                                            }
+                                            None => {}
+                                        }
+                                        if len > 1
+                                            && ui
+                                                .add_enabled(removable, egui::Button::new("X"))
+                                                .on_disabled_hover_text(
+                                                    "全ての行は削除できません",
+                                                )
+                                                .clicked()
+                                        {
+                                            if bulk {
+                                                bulk_remove = true;
+                                            } else {
                                                 invocations.push((
                                                     Box::new(AudioQueryCommands::Remove(0, None)),
                                                     line.clone(),
                                                 ));
                                             }
                                         }
-                                        if let Some(ccn) = chara_change_notify {
-                                            let style_id = ccn.new_chara;
-                                            invocations.push((Box::new(ccn), line.clone()));
-
-                                            log::debug!(
-                                                "send audio query request for {} with id {}.",
+                                        if let (Some(ccn), true) = (&chara_change_notify, bulk) {
+                                            bulk_chara_change = Some(ccn.new_chara);
+                                        } else if let Some(ccn) = chara_change_notify {
+                                            spawn_chara_change_job(
                                                 line,
-                                                style_id
-                                            );
-                                            let (tx, rx) = tokio::sync::oneshot::channel();
-                                            let text = tts_line.text.clone();
-                                            self.audio_query_jobs.insert(
-                                                line.clone(),
-                                                AudioQueryState::WaitingForQuery(text.clone(), rx),
+                                                tts_line,
+                                                ccn.new_chara,
+                                                self.keep_pitch_on_chara_change,
+                                                &mut self.audio_query_jobs,
+                                                &mut self.mora_data_jobs,
                                             );
-                                            tokio::spawn(async move {
-                                                tx.send(
-                                                    api::AudioQuery {
-                                                        text: text.clone(),
-                                                        speaker: style_id,
-                                                        core_version: None,
-                                                    }
-                                                    .call()
-                                                    .await,
-                                                )
-                                                .unwrap();
-                                            });
+                                            invocations.push((Box::new(ccn), line.clone()));
                                         }
                                         if let Some(job) = self.audio_query_jobs.get_mut(line) {
-                                            if let AudioQueryState::WaitingForQuery(
-                                                text,
-                                                ref mut ac,
-                                            ) = job
-                                            {
-                                                if let Ok(aq) = ac.try_recv() {
-                                                    match aq {
-                                                        Ok(aq) => {
-                                                            *job = AudioQueryState::Finished(
-                                                                text.clone(),
-                                                                aq,
-                                                            );
-                                                        }
-                                                        Err(_) => {
-                                                            *job = AudioQueryState::Failed;
-                                                        }
+                                            job.poll();
+                                        }
+                                        match self.audio_query_jobs.get(line) {
+                                            Some(AudioQueryState::WaitingForQuery(..)) => {
+                                                ui.spinner();
+                                            }
+                                            Some(AudioQueryState::Failed(_, reason)) => {
+                                                ui.colored_label(Color32::RED, "⚠")
+                                                    .on_hover_text(reason);
+                                                if ui.button("再試行").clicked() {
+                                                    if let Some(AudioQueryState::Failed(
+                                                        request,
+                                                        _,
+                                                    )) = self.audio_query_jobs.remove(line)
+                                                    {
+                                                        self.audio_query_jobs.insert(
+                                                            line.clone(),
+                                                            spawn_audio_query(
+                                                                request,
+                                                                tts_line.styleId,
+                                                            ),
+                                                        );
                                                     }
-                                                } else {
-                                                    ui.spinner();
                                                 }
-                                            } else if let AudioQueryState::Finished(text, aq) = job
-                                            {
-                                                //inspect history.
-                                                invocations.push((
-                                                    Box::new(
+                                            }
+                                            Some(AudioQueryState::Finished(..)) => {
+                                                // result for text replaced meanwhile is dropped.
+                                                if let Some(AudioQueryState::Finished(request, aq)) =
+                                                    self.audio_query_jobs.remove(line).filter(
+                                                        |state| state.request().text == tts_line.text,
+                                                    )
+                                                {
+                                                    // keep tuning of phrases which are not edited.
+                                                    let accent_phrases = request
+                                                        .base
+                                                        .as_ref()
+                                                        .or_else(|| {
+                                                            tts_line
+                                                                .query
+                                                                .as_ref()
+                                                                .map(|current| &current.accentPhrases)
+                                                        })
+                                                        .map(|base| {
+                                                            commands::carry_over_accent_phrases(
+                                                                base,
+                                                                &aq.accent_phrases,
+                                                            )
+                                                        })
+                                                        .unwrap_or_default();
+                                                    let cmd = Box::new(
                                                         AudioQueryCommands::UpdateAccentPhrases {
-                                                            new_text: text.clone(),
-                                                            accent_phrases: aq
-                                                                .accent_phrases
-                                                                .iter()
-                                                                .map(|ap| ap.clone().into())
-                                                                .collect(),
-                                                            prev_text: self.back_up_text.clone(),
+                                                            new_text: request.text,
+                                                            accent_phrases,
+                                                            prev_text: request.prev_text,
                                                         },
-                                                    ),
-                                                    line.clone(),
-                                                ));
-
-                                                *job = AudioQueryState::NoJob;
+                                                    );
+                                                    if let (true, Some(entry)) =
+                                                        (request.amend, request.entry)
+                                                    {
+                                                        amendments.push((cmd, line.clone(), entry));
+                                                    } else if request.amend {
+                                                        log::debug!(
+                                                            "discard query of unrecorded edit for {}",
+                                                            line
+                                                        );
+                                                    } else {
+                                                        invocations.push((cmd, line.clone()));
+                                                    }
+                                                }
                                             }
+                                            None => {}
                                         }
                                     });
+                                    let hit = matches!(find_pattern, Some(pattern) if pattern.is_match(&tts_line.text));
+                                    let fill = if selected {
+                                        Some(ui.visuals().selection.bg_fill)
+                                    } else if hit {
+                                        Some(Color32::from_rgba_unmultiplied(255, 200, 0, 48))
+                                    } else {
+                                        None
+                                    };
+                                    if let Some(fill) = fill {
+                                        ui.painter().set(
+                                            background,
+                                            egui::Shape::rect_filled(
+                                                row.response.rect.expand(2.0),
+                                                2.0,
+                                                fill,
+                                            ),
+                                        );
+                                    }
+                                    if scroll_to.as_ref() == Some(line) {
+                                        row.response.scroll_to_me(Some(egui::Align::Center));
+                                    }
+                                    row_rects.push(row.response.rect);
+                                }
+                                if let (Some((dragged, dropped)), Some(pos)) =
+                                    (&dragging, ui.input().pointer.interact_pos())
+                                {
+                                    // gap between rows nearest to pointer.
+                                    let to = row_rects
+                                        .iter()
+                                        .filter(|rect| rect.center().y < pos.y)
+                                        .count();
+                                    let spacing = ui.spacing().item_spacing.y / 2.0;
+                                    let y = match row_rects.get(to) {
+                                        Some(rect) => rect.top() - spacing,
+                                        None => row_rects.last().map_or(pos.y, |rect| rect.bottom() + spacing),
+                                    };
+                                    ui.painter().hline(
+                                        ui.max_rect().x_range(),
+                                        y,
+                                        Stroke::new(2.0, ui.visuals().selection.stroke.color),
+                                    );
+                                    if let (true, Some(from)) =
+                                        (*dropped, order.iter().position(|line| line == dragged))
+                                    {
+                                        // index after the line is taken out.
+                                        let to = if to > from { to - 1 } else { to };
+                                        if to != from {
+                                            invocations.push((
+                                                Box::new(AudioQueryCommands::Move { from, to }),
+                                                dragged.clone(),
+                                            ));
+                                        }
+                                    }
                                 }
                             });
 
+                            let selected_lines =
+                                self.selection.ordered(&self.histories.project.audioKeys);
+                            if let Some(style_id) = bulk_chara_change {
+                                for line in &selected_lines {
+                                    if let Some(ai) = self.histories.project.audioItems.get(line) {
+                                        if ai.styleId == style_id {
+                                            continue;
+                                        }
+                                        invocations.push((
+                                            Box::new(CharaChangeCommand {
+                                                prev_chara: ai.styleId,
+                                                new_chara: style_id,
+                                            }),
+                                            line.clone(),
+                                        ));
+                                        spawn_chara_change_job(
+                                            line,
+                                            ai,
+                                            style_id,
+                                            self.keep_pitch_on_chara_change,
+                                            &mut self.audio_query_jobs,
+                                            &mut self.mora_data_jobs,
+                                        );
+                                    }
+                                }
+                                transaction_name = Some("キャラクター一括変更");
+                            }
+                            // button is disabled while every line is selected.
+                            if bulk_remove
+                                && selected_lines.len() < self.histories.project.audioKeys.len()
+                            {
+                                for line in selected_lines {
+                                    invocations.push((
+                                        Box::new(AudioQueryCommands::Remove(0, None)),
+                                        line,
+                                    ));
+                                }
+                                transaction_name = Some("行一括削除");
+                            }
+
                             let top_left = bottom_right - egui::vec2(64.0, 64.0);
                             let center = bottom_right - egui::vec2(32.0, 32.0);
                             let response = ui.allocate_rect(
@@ -508,6 +1504,7 @@ impl eframe::App for VoiceVoxRust {
                                 log::debug!("new uuid {}", uuid);
                                 invocations.push((
                                     Box::new(AudioQueryCommands::Insert(
+                                        self.index_after(&self.current_selected_tts_line),
                                         crate::project::AudioItem {
                                             text: "".to_string(),
                                             styleId: 2,
@@ -515,6 +1512,8 @@ impl eframe::App for VoiceVoxRust {
                                                 BLANK_AUDIO_QUERY.get().cloned().unwrap().into(),
                                             ),
                                             presetKey: None,
+                                            morphingInfo: None,
+                                            interrogativeUpspeak: None,
                                         },
                                     )),
                                     uuid,
@@ -524,8 +1523,66 @@ impl eframe::App for VoiceVoxRust {
                     });
                 });
 
-                for invocation in invocations {
-                    self.histories.invoke(invocation.0, invocation.1);
+                if let Some(name) = transaction_name {
+                    self.histories.begin_transaction(name);
+                }
+                // entry which recorded the last edit of each line.
+                let mut entries = HashMap::new();
+                for (command, line) in invocations {
+                    let entry = self.histories.invoke(command, line.clone());
+                    self.bind_jobs(&line, entry);
+                    entries.insert(line, entry);
+                }
+                if transaction_name.is_some() {
+                    self.histories.commit_transaction();
+                }
+                for (command, line, entry) in amendments {
+                    self.histories.amend(command, line, entry);
+                }
+                if let Some((op, line)) = ctx_menu_op {
+                    self.apply_context_menu_op(op, &line);
+                }
+                if let Some(changes) = replacements {
+                    self.replace_texts(changes);
+                }
+                self.selection.retain(&self.histories.project.audioKeys);
+
+                if let Some((line, affected)) = mora_data_request {
+                    if let Some(ai) = self.histories.project.audioItems.get(&line) {
+                        if let Some(aq) = &ai.query {
+                            log::debug!("send mora data request for {}", line);
+                            let mut job = spawn_mora_data(
+                                aq.accentPhrases.clone(),
+                                ai.styleId,
+                                affected,
+                                None,
+                            );
+                            job.entry = entries.get(&line).copied();
+                            self.mora_data_jobs.insert(line, job);
+                        }
+                    }
+                }
+
+                let mut show_history = self.show_history;
+                let history_op = egui::Window::new("履歴")
+                    .open(&mut show_history)
+                    .show(ctx, |ui| {
+                        crate::history_panel::create_history_panel(ui, &self.histories)
+                    })
+                    .and_then(|res| res.inner)
+                    .flatten();
+                self.show_history = show_history;
+                match history_op {
+                    Some(HistoryPanelOp::Undo(count)) => {
+                        self.histories.undo_n(count);
+                        self.cancel_outdated_queries();
+                    }
+                    Some(HistoryPanelOp::Redo(count)) => {
+                        self.histories.redo_n(count);
+                        self.cancel_outdated_queries();
+                    }
+                    Some(HistoryPanelOp::Clear) => self.histories.clear(),
+                    None => {}
                 }
             }
             CurrentView::ToolBarCustomize => {
@@ -604,7 +1661,7 @@ impl eframe::App for VoiceVoxRust {
                                 let text = self
                                     .tool_bar_config_editing
                                     .get(index)
-                                    .map(|op| &crate::tool_bar::TOOL_BAR_OPS[&op]);
+                                    .map(|op| &crate::tool_bar::TOOL_BAR_OPS[op]);
                                 ui.horizontal(|ui| {
                                     if let Some(text) = text {
                                         ui.label(
@@ -716,6 +1773,7 @@ impl eframe::App for VoiceVoxRust {
                     None => {}
                     Some(true) => {
                         self.histories = history::HistoryManager::new();
+                        self.cancel_all_jobs();
                         self.opening_dialogues = None;
                         self.opening_file = None;
                         self.block_menu_control = false;
@@ -726,21 +1784,63 @@ impl eframe::App for VoiceVoxRust {
                     }
                 }
             }
+            Some(DialogueKind::AskSaveOnExit) => {
+                let mut cell: Option<SaveOnExit> = None;
+                let dialogue = dialogue::Dialogue {
+                    title: "警告",
+                    text: "プロジェクトの変更が保存されていません.\n保存して終了しますか.",
+                    control_constructor: Box::new(crate::dialogue::SaveOnExitControl {}),
+                    cell: Some(&mut cell),
+                };
+                dialogue.show(ctx);
+                match cell {
+                    None => {}
+                    Some(SaveOnExit::Save) => {
+                        self.opening_dialogues = None;
+                        self.block_menu_control = false;
+                        if self.overwrite_project() {
+                            self.exit_confirmed = true;
+                            frame.quit();
+                        }
+                    }
+                    Some(SaveOnExit::Discard) => {
+                        self.opening_dialogues = None;
+                        self.exit_confirmed = true;
+                        frame.quit();
+                    }
+                    Some(SaveOnExit::Cancel) => {
+                        self.opening_dialogues = None;
+                        self.block_menu_control = false;
+                    }
+                }
+            }
         }
 
-        for synthesis_state in self.synthesis_cache.values_mut() {
-            match synthesis_state {
-                SynthesisState::WaitingForSynthesis(rx) => match rx.try_recv() {
-                    Ok(v) => match v {
-                        Ok(v) => *synthesis_state = SynthesisState::Finished(Cursor::new(v)),
-                        Err(_) => *synthesis_state = SynthesisState::Failed,
-                    },
-                    Err(TryRecvError::Closed) => *synthesis_state = SynthesisState::Failed,
-                    Err(TryRecvError::Empty) => {}
-                },
-                SynthesisState::Finished(_) => {}
-                _ => {}
-            }
+        self.cancel_stale_jobs();
+        self.poll_mora_data_jobs();
+        self.poll_export_jobs();
+        self.synthesis_cache.poll();
+        self.player.poll(&mut self.synthesis_cache);
+        if self.player.is_active() {
+            ctx.request_repaint();
+        }
+        if self.pre_synthesis.schedule(
+            &self.histories.project,
+            &self.current_selected_tts_line,
+            &mut self.synthesis_cache,
+        ) {
+            ctx.request_repaint();
+        }
+    }
+
+    fn on_exit_event(&mut self) -> bool {
+        if self.exit_confirmed || self.histories.saved() {
+            true
+        } else {
+            log::trace!("opening save on exit dialogue");
+            self.opening_dialogues = Some(DialogueKind::AskSaveOnExit);
+            self.block_menu_control = true;
+            false
         }
     }
 }
@@ -749,6 +1849,9 @@ async fn main() {
     simple_log::console("debug").unwrap();
     init_blank_audio_query().await;
     chara_change_button::init_icon_store().await;
+    if synthesis::init_disk_cache().await.is_none() {
+        log::warn!("on-disk synthesis cache is unavailable");
+    }
     let mut app = VoiceVoxRust::new().await;
 
     eframe::run_native(
@@ -763,3 +1866,48 @@ async fn main() {
         }),
     );
 }
+
+#[cfg(test)]
+mod test {
+    use super::{file_name_of, replace_text, FILE_NAME_LEN};
+    use crate::history::HistoryManager;
+    use crate::project::test::sample_project;
+    use crate::project::VoiceVoxProjectFile;
+
+    #[test]
+    fn file_name_without_reserved_chars() {
+        assert_eq!(
+            file_name_of("a/b\\c:d?e*f\"g<h>i|j\nk\r\tl"),
+            "a_b_c_d_e_f_g_h_i_j_k__l"
+        );
+        assert_eq!(file_name_of("../終わり. "), ".._終わり");
+        assert_eq!(file_name_of(".."), "_");
+        let long = "あ".repeat(FILE_NAME_LEN * 2);
+        assert_eq!(file_name_of(&long).chars().count(), FILE_NAME_LEN);
+    }
+
+    #[test]
+    fn undo_before_replaced_query_arrives() {
+        let mut histories = HistoryManager::from_project_file(VoiceVoxProjectFile {
+            project: sample_project(),
+            history: None,
+        });
+        let request = replace_text(&mut histories, "line", "置換後".to_owned()).unwrap();
+        assert_eq!(request.entry, Some(histories.undo_stack()[0].id));
+        let line = &histories.project.audioItems["line"];
+        assert_eq!(line.text, "置換後");
+        // audio of old text is not kept while waiting.
+        assert!(line.query.as_ref().unwrap().accentPhrases.is_empty());
+
+        histories.undo();
+        assert!(histories.project == sample_project());
+        // result arriving now is dropped as its text is outdated.
+        assert_ne!(histories.project.audioItems["line"].text, request.text);
+        assert_eq!(histories.redo_stack().len(), 1);
+
+        histories.redo();
+        assert_eq!(histories.project.audioItems["line"].text, "置換後");
+        assert!(replace_text(&mut histories, "line", String::new()).is_none());
+        assert_eq!(histories.project.audioItems["line"].text, "");
+    }
+}
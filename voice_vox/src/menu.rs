@@ -1,4 +1,6 @@
+use crate::synthesis::USE_DISK_CACHE;
 use eframe::egui::Ui;
+use std::sync::atomic::Ordering;
 
 pub enum TopMenuOp {
     NewProject,
@@ -9,6 +11,10 @@ pub enum TopMenuOp {
     OverwriteProject,
     SaveProjectAs,
     LoadProject,
+    Undo,
+    Redo,
+    Find,
+    History,
     RebootEngine,
     KeyConfig,
     ToolBarCustomize,
@@ -19,7 +25,7 @@ pub enum TopMenuOp {
     Help,
 }
 
-pub fn create_menu_bar(ui: &mut Ui) -> Option<TopMenuOp> {
+pub fn create_menu_bar(ui: &mut Ui, keep_pitch_on_chara_change: &mut bool) -> Option<TopMenuOp> {
     ui.horizontal(|ui| {
         let mut op = None;
         ui.menu_button("ファイル", |ui| {
@@ -49,6 +55,27 @@ pub fn create_menu_bar(ui: &mut Ui) -> Option<TopMenuOp> {
                 op = Some(TopMenuOp::LoadProject);
             }
         });
+        ui.menu_button("編集", |ui| {
+            if ui.button("元に戻す").clicked() {
+                op = Some(TopMenuOp::Undo);
+            }
+            if ui.button("やり直す").clicked() {
+                op = Some(TopMenuOp::Redo);
+            }
+            ui.separator();
+            if ui.button("検索と置換").clicked() {
+                op = Some(TopMenuOp::Find);
+            }
+            ui.separator();
+            ui.checkbox(
+                keep_pitch_on_chara_change,
+                "キャラクター変更時に音高の調整を保持",
+            );
+            ui.separator();
+            if ui.button("履歴").clicked() {
+                op = Some(TopMenuOp::History);
+            }
+        });
         ui.menu_button("エンジン", |ui| {
             if ui.button("再起動").clicked() {
                 op = Some(TopMenuOp::RebootEngine);
@@ -61,6 +88,13 @@ pub fn create_menu_bar(ui: &mut Ui) -> Option<TopMenuOp> {
             if ui.button("ツールバーのカスタマイズ").clicked() {
                 op = Some(TopMenuOp::ToolBarCustomize)
             }
+            let mut use_disk_cache = USE_DISK_CACHE.load(Ordering::Relaxed);
+            if ui
+                .checkbox(&mut use_disk_cache, "合成音声をディスクにキャッシュ")
+                .changed()
+            {
+                USE_DISK_CACHE.store(use_disk_cache, Ordering::Relaxed);
+            }
             if ui.button("キャラクター並び替え・試聴").clicked() {
                 op = Some(TopMenuOp::SampleVoice)
             }
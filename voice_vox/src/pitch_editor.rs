@@ -0,0 +1,259 @@
+use crate::commands::BottomPaneCommand;
+use eframe::egui::{
+    pos2, vec2, Align2, Color32, FontId, Pos2, Rect, Sense, Shape, Stroke, TextStyle, Ui,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeInclusive;
+use voice_vox_api::api_schema::AccentPhraseInProject;
+
+/// pitch shown in editor. same range as former intonation sliders.
+pub const PITCH_RANGE: RangeInclusive<f32> = 3.0..=6.5;
+/// mora pitch is natural log of frequency.
+const SEMITONE: f32 = std::f32::consts::LN_2 / 12.0;
+const MORA_WIDTH: f32 = 40.0;
+const HEIGHT: f32 = 160.0;
+const POINT_RADIUS: f32 = 5.0;
+
+/// (accent phrase, mora)
+type MoraIndex = (usize, usize);
+
+/// piano roll like editor of mora pitches.
+///
+/// edits are previewed while dragging and emitted as single command on release.
+/// clicking mora text toggles devoicing.
+#[derive(Default)]
+pub struct PitchEditor {
+    selected: BTreeSet<MoraIndex>,
+    /// paint pitches by sweeping mouse instead of moving selected points.
+    draw_mode: bool,
+    snap: bool,
+    /// edited pitches while dragging.
+    preview: BTreeMap<MoraIndex, f32>,
+    /// pointer position of previous frame in draw mode.
+    last_draw: Option<Pos2>,
+}
+
+pub fn snap_to_semitone(pitch: f32) -> f32 {
+    (pitch / SEMITONE).round() * SEMITONE
+}
+
+impl PitchEditor {
+    pub fn ui(
+        &mut self,
+        ui: &mut Ui,
+        edit_targets: &[AccentPhraseInProject],
+    ) -> Option<BottomPaneCommand> {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.draw_mode, false, "選択");
+            ui.selectable_value(&mut self.draw_mode, true, "描画");
+            ui.checkbox(&mut self.snap, "半音にスナップ");
+        });
+
+        let moras: Vec<(MoraIndex, &str, f32)> = edit_targets
+            .iter()
+            .enumerate()
+            .flat_map(|(ap, target)| {
+                target
+                    .moras
+                    .iter()
+                    .enumerate()
+                    .map(move |(m, mora)| ((ap, m), mora.text.as_str(), mora.pitch))
+            })
+            .collect();
+        // selection of previous line or before structural edits.
+        self.selected
+            .retain(|index| moras.iter().any(|(i, _, _)| i == index));
+
+        let width = moras.len() as f32 * MORA_WIDTH;
+        let (response, painter) = ui.allocate_painter(vec2(width, HEIGHT), Sense::click_and_drag());
+        let rect = response.rect;
+        let graph = Rect::from_min_max(
+            rect.min,
+            pos2(
+                rect.right(),
+                rect.bottom() - ui.text_style_height(&TextStyle::Body),
+            ),
+        );
+        let (low, high) = (*PITCH_RANGE.start(), *PITCH_RANGE.end());
+        let to_y = |pitch: f32| graph.bottom() - (pitch - low) / (high - low) * graph.height();
+        let to_pitch =
+            |y: f32| (low + (graph.bottom() - y) / graph.height() * (high - low)).clamp(low, high);
+        let to_x = |i: usize| rect.left() + (i as f32 + 0.5) * MORA_WIDTH;
+        let mora_at = |x: f32| {
+            let i = ((x - rect.left()) / MORA_WIDTH).floor();
+            if i >= 0.0 && (i as usize) < moras.len() {
+                Some(i as usize)
+            } else {
+                None
+            }
+        };
+        let hit = |pos: Pos2| {
+            moras.iter().enumerate().position(|(i, (_, _, pitch))| {
+                *pitch > 0.0 && pos2(to_x(i), to_y(*pitch)).distance(pos) <= POINT_RADIUS * 2.0
+            })
+        };
+        let snap = self.snap;
+        let adjust = |pitch: f32| {
+            let pitch = pitch.clamp(low, high);
+            if snap {
+                snap_to_semitone(pitch)
+            } else {
+                pitch
+            }
+        };
+
+        let mut rt = None;
+        let on_label = |pos: Pos2| pos.y > graph.bottom();
+        if let (true, Some(pos)) = (response.clicked(), response.interact_pointer_pos()) {
+            if let Some(i) = mora_at(pos.x).filter(|_| on_label(pos)) {
+                let ((ap, m), _, _) = moras[i];
+                rt = BottomPaneCommand::toggle_devoice(edit_targets, ap, m);
+                self.selected.remove(&(ap, m));
+            }
+        }
+        // presses on mora text are only for devoicing.
+        let from_label =
+            matches!(ui.input().pointer.press_origin(), Some(origin) if on_label(origin));
+        if let Some(pos) = response
+            .interact_pointer_pos()
+            .filter(|_| rt.is_none() && !from_label)
+        {
+            if self.draw_mode {
+                // fill moras swept between frames.
+                let from = self.last_draw.unwrap_or(pos);
+                let (start, end) = if from.x <= pos.x {
+                    (from, pos)
+                } else {
+                    (pos, from)
+                };
+                if let (Some(a), Some(b)) = (
+                    mora_at(start.x.max(rect.left())),
+                    mora_at(end.x.min(rect.right() - 1.0)),
+                ) {
+                    for (i, (index, _, pitch)) in moras.iter().enumerate().take(b + 1).skip(a) {
+                        if *pitch <= 0.0 {
+                            continue;
+                        }
+                        let t = if end.x > start.x {
+                            ((to_x(i) - start.x) / (end.x - start.x)).clamp(0.0, 1.0)
+                        } else {
+                            1.0
+                        };
+                        let y = start.y + (end.y - start.y) * t;
+                        self.preview.insert(*index, adjust(to_pitch(y)));
+                    }
+                }
+                self.last_draw = Some(pos);
+            } else if response.drag_started() || response.clicked() {
+                let shift = ui.input().modifiers.shift;
+                match hit(pos) {
+                    Some(i) if shift => {
+                        let index = moras[i].0;
+                        if !self.selected.remove(&index) {
+                            self.selected.insert(index);
+                        }
+                    }
+                    Some(i) if !self.selected.contains(&moras[i].0) => {
+                        self.selected = BTreeSet::from([moras[i].0]);
+                    }
+                    Some(_) => {}
+                    None if !shift => self.selected.clear(),
+                    None => {}
+                }
+            } else if response.dragged() {
+                let origin = ui.input().pointer.press_origin().unwrap_or(pos);
+                let diff = to_pitch(pos.y) - to_pitch(origin.y);
+                for (index, _, pitch) in &moras {
+                    if self.selected.contains(index) && *pitch > 0.0 {
+                        self.preview.insert(*index, adjust(pitch + diff));
+                    }
+                }
+            }
+        }
+
+        if rt.is_none() && (response.drag_released() || (self.draw_mode && response.clicked())) {
+            let diffs: Vec<(usize, usize, f32)> = moras
+                .iter()
+                .filter_map(|((ap, m), _, pitch)| {
+                    let next = self.preview.get(&(*ap, *m))?;
+                    ((next - pitch).abs() > f32::EPSILON).then_some((*ap, *m, next - pitch))
+                })
+                .collect();
+            rt = match diffs.as_slice() {
+                [] => None,
+                [(accent_phrase, mora, pitch_diff)] => Some(BottomPaneCommand::Pitch {
+                    accent_phrase: *accent_phrase,
+                    mora: *mora,
+                    pitch_diff: *pitch_diff,
+                }),
+                _ => Some(BottomPaneCommand::Pitches { diffs }),
+            };
+            self.preview.clear();
+            self.last_draw = None;
+        }
+
+        let visuals = ui.visuals();
+        painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+        let grid = Stroke::new(1.0, visuals.faint_bg_color);
+        let mut semitone = snap_to_semitone(low);
+        while semitone <= high {
+            painter.hline(graph.x_range(), to_y(semitone), grid);
+            semitone += SEMITONE;
+        }
+        let boundary = Stroke::new(1.0, visuals.widgets.noninteractive.bg_stroke.color);
+        let curve = Stroke::new(2.0, visuals.text_color());
+        let mut points = Vec::new();
+        for (i, (index, text, pitch)) in moras.iter().enumerate() {
+            let pitch = self.preview.get(index).copied().unwrap_or(*pitch);
+            if index.1 == 0 && i > 0 {
+                painter.vline(to_x(i) - MORA_WIDTH / 2.0, rect.y_range(), boundary);
+            }
+            // unvoiced moras are grayed out and break curve.
+            let text_color = if pitch <= 0.0 {
+                visuals.weak_text_color()
+            } else {
+                visuals.text_color()
+            };
+            painter.text(
+                pos2(to_x(i), rect.bottom()),
+                Align2::CENTER_BOTTOM,
+                text,
+                FontId::default(),
+                text_color,
+            );
+            if pitch <= 0.0 {
+                painter.add(Shape::line(std::mem::take(&mut points), curve));
+                painter.circle_stroke(
+                    pos2(to_x(i), graph.bottom()),
+                    POINT_RADIUS,
+                    Stroke::new(1.0, text_color),
+                );
+                continue;
+            }
+            let point = pos2(to_x(i), to_y(pitch));
+            points.push(point);
+            let color = if self.selected.contains(index) {
+                Color32::LIGHT_BLUE
+            } else {
+                visuals.text_color()
+            };
+            painter.circle_filled(point, POINT_RADIUS, color);
+        }
+        painter.add(Shape::line(points, curve));
+        rt
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{snap_to_semitone, SEMITONE};
+
+    #[test]
+    fn snap_to_nearest_semitone() {
+        let pitch = 5.0;
+        let snapped = snap_to_semitone(pitch);
+        assert!((snapped - pitch).abs() <= SEMITONE / 2.0);
+        assert!((snap_to_semitone(snapped + SEMITONE * 0.4) - snapped).abs() < 1e-5);
+        assert!((snap_to_semitone(snapped + SEMITONE * 0.6) - snapped - SEMITONE).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::synthesis_cache::{SynthesisCache, SynthesisKey, SynthesisState};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+use std::io::Cursor;
+use std::time::Instant;
+
+struct Playing {
+    key: SynthesisKey,
+    sink: Sink,
+    started: Instant,
+}
+
+/// plays synthesized audio of one line at a time.
+#[derive(Default)]
+pub struct Player {
+    /// opened on first playback. sound stops when stream is dropped.
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    playing: Option<Playing>,
+    /// audio to play once synthesis finishes.
+    pending: Option<SynthesisKey>,
+}
+
+impl Player {
+    /// play audio of `key` once it is synthesized.
+    pub fn queue(&mut self, key: SynthesisKey) {
+        self.stop();
+        self.pending = Some(key);
+    }
+
+    pub fn stop(&mut self) {
+        self.pending = None;
+        if let Some(playing) = self.playing.take() {
+            playing.sink.stop();
+        }
+    }
+
+    /// start pending playback if synthesis finished.
+    pub fn poll(&mut self, cache: &mut SynthesisCache) {
+        if matches!(&self.playing, Some(playing) if playing.sink.empty()) {
+            self.playing = None;
+        }
+        let key = if let Some(key) = self.pending {
+            key
+        } else {
+            return;
+        };
+        match cache.get(&key) {
+            Some(SynthesisState::WaitingForSynthesis(_)) => {}
+            Some(SynthesisState::Finished(wav)) => {
+                let wav = wav.get_ref().clone();
+                self.pending = None;
+                if let Err(e) = self.play(key, wav) {
+                    log::error!("playback failed {}", e);
+                }
+            }
+            // failed or cancelled.
+            _ => self.pending = None,
+        }
+    }
+
+    fn play(&mut self, key: SynthesisKey, wav: Vec<u8>) -> Result<(), String> {
+        if self.output.is_none() {
+            self.output = Some(OutputStream::try_default().map_err(|e| format!("{:?}", e))?);
+        }
+        let (_, handle) = self.output.as_ref().unwrap();
+        let sink = Sink::try_new(handle).map_err(|e| format!("{:?}", e))?;
+        sink.append(Decoder::new_wav(Cursor::new(wav)).map_err(|e| format!("{:?}", e))?);
+        self.playing = Some(Playing {
+            key,
+            sink,
+            started: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// true while waiting for synthesis or playing.
+    pub fn is_active(&self) -> bool {
+        self.pending.is_some() || self.playing.is_some()
+    }
+
+    /// seconds since playback of `key` started.
+    pub fn position(&self, key: &SynthesisKey) -> Option<f32> {
+        match &self.playing {
+            Some(playing) if playing.key == *key => Some(playing.started.elapsed().as_secs_f32()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::project::VoiceVoxProject;
+use crate::synthesis_cache::{SynthesisCache, SynthesisKey};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// wait after last change of line before synthesizing it.
+const DEBOUNCE: Duration = Duration::from_millis(800);
+
+/// engine processes synthesis one by one, so only few requests are kept in flight.
+const MAX_IN_FLIGHT: usize = 2;
+
+/// synthesize settled lines in background so playback starts without waiting.
+#[derive(Default)]
+pub struct PreSynthesis {
+    /// last seen key of each line and when it changed.
+    seen: HashMap<String, (SynthesisKey, Instant)>,
+}
+
+impl PreSynthesis {
+    /// request synthesis of settled lines, nearest to `focus` first.
+    ///
+    /// returns true while some line is waiting for debounce or synthesis,
+    /// so that caller keeps repainting.
+    pub fn schedule(
+        &mut self,
+        project: &VoiceVoxProject,
+        focus: &str,
+        cache: &mut SynthesisCache,
+    ) -> bool {
+        let now = Instant::now();
+        let mut unsettled = false;
+        let mut candidates = Vec::new();
+        for (index, line) in project.audioKeys.iter().enumerate() {
+            let item = &project.audioItems[line];
+            let key = match SynthesisKey::of(item) {
+                Some(key) if !item.text.is_empty() => key,
+                _ => continue,
+            };
+            let changed = match self.seen.get_mut(line) {
+                Some((seen, changed)) if *seen == key => *changed,
+                Some(seen) => {
+                    *seen = (key, now);
+                    now
+                }
+                None => {
+                    self.seen.insert(line.clone(), (key, now));
+                    now
+                }
+            };
+            if now.duration_since(changed) < DEBOUNCE {
+                unsettled = true;
+            } else if cache.peek(&key).is_none() {
+                // failed lines wait for retry by user.
+                candidates.push((index, line, item));
+            }
+        }
+        self.seen
+            .retain(|line, _| project.audioItems.contains_key(line));
+
+        let focus = project
+            .audioKeys
+            .iter()
+            .position(|line| line == focus)
+            .unwrap_or(0);
+        candidates.sort_by_key(|(index, _, _)| (*index as isize - focus as isize).abs());
+        for (_, line, item) in candidates {
+            if cache.in_flight() >= MAX_IN_FLIGHT || !cache.has_room() {
+                break;
+            }
+            cache.request(line, item);
+        }
+        unsettled || cache.in_flight() > 0
+    }
+}
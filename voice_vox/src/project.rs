@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 
 use crate::api_schema;
+use crate::history::ProjectHistory;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
@@ -11,9 +13,23 @@ pub struct AudioItem {
     pub query: Option<api_schema::AudioQueryInProject>,
     #[cfg_attr(feature = "vv0114", serde(skip))]
     pub presetKey: Option<String>,
+    #[cfg_attr(feature = "vv0114", serde(skip))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub morphingInfo: Option<MorphingInfo>,
+    /// raise end of interrogative accent phrases. `None` follows engine default.
+    #[cfg_attr(feature = "vv0114", serde(skip))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interrogativeUpspeak: Option<bool>,
 }
 
-const DEFAULT_SAMPLING_RATE: i64 = 24000;
+/// synthesize line as morphing from `styleId` of the line into `targetStyleId`.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MorphingInfo {
+    /// 0.0 is base style and 1.0 is target style.
+    pub rate: f64,
+    pub targetStyleId: i32,
+}
 
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
@@ -22,3 +38,206 @@ pub struct VoiceVoxProject {
     pub audioKeys: Vec<String>,
     pub audioItems: HashMap<String, AudioItem>,
 }
+
+/// project with its undo/redo history.
+///
+/// `.vvproj` holds project only, so that official editor which validates the file strictly can open it.
+/// history is stored in separate file given by [history_path].
+pub struct VoiceVoxProjectFile {
+    pub project: VoiceVoxProject,
+    pub history: Option<ProjectHistory>,
+}
+
+/// history file stored next to project file `path`. `a.vvproj` has `a.vvproj.history`.
+pub fn history_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".history");
+    path.with_file_name(name)
+}
+
+impl VoiceVoxProjectFile {
+    /// parse contents of project file and history file.
+    ///
+    /// history written by older or other versions is dropped instead of failing whole project.
+    pub fn from_json(project: &str, history: Option<&str>) -> Result<Self, serde_json::Error> {
+        let project = serde_json::from_str(project)?;
+        let history = history.and_then(|history| match serde_json::from_str(history) {
+            Ok(history) => Some(history),
+            Err(e) => {
+                log::warn!("ignore unreadable history {}", e);
+                None
+            }
+        });
+        Ok(Self { project, history })
+    }
+
+    /// contents of project file and history file. history is `None` while it is empty.
+    pub fn to_json(&self) -> Result<(String, Option<String>), serde_json::Error> {
+        let project = serde_json::to_string(&self.project)?;
+        let history = self
+            .history
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        Ok((project, history))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{history_path, AudioItem, VoiceVoxProject, VoiceVoxProjectFile};
+    use crate::api_schema::{AccentPhraseInProject, AudioQueryInProject, MoraInProject};
+    use crate::commands::{AudioQueryEditCommand, BottomPaneCommand};
+    use crate::history::{CommandRecord, HistoryManager, HistoryRecord, ProjectHistory};
+    use std::collections::HashMap;
+
+    pub(crate) fn sample_project() -> VoiceVoxProject {
+        let query = AudioQueryInProject {
+            accentPhrases: vec![AccentPhraseInProject {
+                moras: vec![MoraInProject {
+                    text: "ア".to_string(),
+                    consonant: None,
+                    consonantLength: None,
+                    vowel: "a".to_string(),
+                    vowelLength: 0.1,
+                    pitch: 5.0,
+                }],
+                accent: 1,
+                pause_mora: None,
+                isInterrogative: Some(false),
+            }],
+            speedScale: 1.0,
+            pitchScale: 0.0,
+            intonationScale: 1.0,
+            volumeScale: 1.0,
+            prePhonemeLength: 0.1,
+            postPhonemeLength: 0.1,
+            outputSamplingRate: 24000,
+            outputStereo: false,
+            kana: None,
+        };
+        let mut items = HashMap::new();
+        items.insert(
+            "line".to_string(),
+            AudioItem {
+                text: "あ".to_string(),
+                styleId: 2,
+                query: Some(query),
+                presetKey: None,
+                morphingInfo: None,
+                interrogativeUpspeak: None,
+            },
+        );
+        VoiceVoxProject {
+            appVersion: "0.11.4".to_string(),
+            audioKeys: vec!["line".to_string()],
+            audioItems: items,
+        }
+    }
+
+    #[test]
+    fn load_official_project() {
+        let json = serde_json::to_string(&sample_project()).unwrap();
+        let file = VoiceVoxProjectFile::from_json(&json, None).unwrap();
+        assert!(file.history.is_none());
+        assert!(file.project == sample_project());
+        let (_, history) = file.to_json().unwrap();
+        assert!(history.is_none());
+        assert_eq!(
+            history_path(std::path::Path::new("dir/a.vvproj")),
+            std::path::Path::new("dir/a.vvproj.history")
+        );
+    }
+
+    fn edited() -> HistoryManager {
+        let mut histories = HistoryManager::from_project_file(VoiceVoxProjectFile {
+            project: sample_project(),
+            history: None,
+        });
+        histories.invoke(
+            Box::new(AudioQueryEditCommand::SpeedScale(0.5)),
+            "line".to_string(),
+        );
+        histories.invoke(
+            Box::new(AudioQueryEditCommand::PitchScale(0.1)),
+            "line".to_string(),
+        );
+        histories
+    }
+
+    #[test]
+    fn history_round_trip() {
+        let mut histories = edited();
+        histories.undo();
+        let (project, history) = histories.to_project_file().to_json().unwrap();
+
+        let file = VoiceVoxProjectFile::from_json(&project, history.as_deref()).unwrap();
+        let mut restored = HistoryManager::from_project_file(file);
+        assert!(restored.saved());
+        assert!(!restored.undo_stack().is_empty());
+        assert!(!restored.redo_stack().is_empty());
+        restored.redo();
+        restored.undo();
+        restored.undo();
+        assert!(restored.project == sample_project());
+    }
+
+    /// project schema of official editor which rejects unknown fields.
+    #[allow(non_snake_case, dead_code)]
+    #[derive(serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct OfficialProject {
+        appVersion: String,
+        audioKeys: Vec<String>,
+        audioItems: HashMap<String, serde_json::Value>,
+    }
+
+    #[test]
+    fn project_with_history_keeps_official_schema() {
+        let (project, history) = edited().to_project_file().to_json().unwrap();
+        assert!(history.is_some());
+        assert!(serde_json::from_str::<OfficialProject>(&project).is_ok());
+    }
+
+    #[test]
+    fn unreadable_or_stale_history_is_dropped() {
+        let json = serde_json::to_string(&sample_project()).unwrap();
+        let file = VoiceVoxProjectFile::from_json(&json, Some(r#"{"undo_stack":[1]}"#)).unwrap();
+        assert!(file.history.is_none());
+        assert!(file.project == sample_project());
+
+        // history of other project refers lines which do not exist.
+        let mut other = sample_project();
+        let item = other.audioItems.remove("line").unwrap();
+        other.audioItems.insert("other".to_owned(), item);
+        other.audioKeys = vec!["other".to_owned()];
+        let (_, history) = edited().to_project_file().to_json().unwrap();
+        let json = serde_json::to_string(&other).unwrap();
+        let file = VoiceVoxProjectFile::from_json(&json, history.as_deref()).unwrap();
+        assert!(file.history.is_some());
+        let mut restored = HistoryManager::from_project_file(file);
+        assert!(restored.undo_stack().is_empty());
+        restored.undo();
+        assert!(restored.project == other);
+
+        // pitch of mora which does not exist.
+        let history = ProjectHistory {
+            undo_stack: vec![HistoryRecord {
+                command: CommandRecord::BottomPane(BottomPaneCommand::Pitch {
+                    accent_phrase: 5,
+                    mora: 0,
+                    pitch_diff: 1.0,
+                }),
+                uuid: "line".to_owned(),
+                time: std::time::SystemTime::now(),
+            }],
+            redo_stack: vec![],
+        };
+        assert!(!history.fits(&sample_project()));
+        let restored = HistoryManager::from_project_file(VoiceVoxProjectFile {
+            project: sample_project(),
+            history: Some(history),
+        });
+        assert!(restored.undo_stack().is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::api::Api;
+use crate::commands::{carry_over_kana_phrases, AudioQueryCommands};
+use crate::job::Job;
+use crate::project::AudioItem;
+use eframe::egui::{Color32, TextEdit, Ui};
+use tokio::sync::oneshot::error::TryRecvError;
+use voice_vox_api::api;
+use voice_vox_api::api_schema::AccentPhraseInProject;
+use voice_vox_api::kana::{parse_kana, to_kana, KanaPhrase};
+
+struct ReadingJob {
+    /// accent phrases when request was sent. result is discarded if line is edited meanwhile.
+    sent: Vec<AccentPhraseInProject>,
+    speaker: i32,
+    job: Job<<api::AccentPhrases as Api>::Response>,
+}
+
+/// "読み" field of current line in AquesTalk like kana.
+///
+/// edited kana is checked locally and sent to engine when field loses focus.
+#[derive(Default)]
+pub struct ReadingEditor {
+    /// line whose reading is shown. request in flight is for this line.
+    line: String,
+    /// kana typed by user. kept while editing, in flight or rejected.
+    editing: Option<String>,
+    error: Option<String>,
+    job: Option<ReadingJob>,
+}
+
+fn reading_of(accent_phrases: &[AccentPhraseInProject]) -> String {
+    to_kana(
+        &accent_phrases
+            .iter()
+            .map(KanaPhrase::from)
+            .collect::<Vec<_>>(),
+    )
+}
+
+impl ReadingEditor {
+    /// returns command replacing accent phrases when engine accepted edited kana.
+    pub fn ui(
+        &mut self,
+        ui: &mut Ui,
+        line: &str,
+        audio_item: &AudioItem,
+        accent_phrases: &[AccentPhraseInProject],
+    ) -> Option<AudioQueryCommands> {
+        if self.line != line {
+            self.line = line.to_owned();
+            self.editing = None;
+            self.error = None;
+            self.job = None;
+        }
+        let rt = self.poll(audio_item, accent_phrases);
+
+        let current = reading_of(accent_phrases);
+        let mut text = self.editing.clone().unwrap_or_else(|| current.clone());
+        ui.horizontal(|ui| {
+            ui.label("読み");
+            let res =
+                ui.add(TextEdit::singleline(&mut text).desired_width(ui.available_width() - 64.0));
+            if res.changed() {
+                self.error = None;
+            }
+            if res.has_focus() {
+                self.editing = Some(text.clone());
+            }
+            if res.lost_focus() {
+                if text == current {
+                    self.editing = None;
+                } else {
+                    match parse_kana(&text) {
+                        Ok(_) => {
+                            log::debug!("send accent phrases request for {}", line);
+                            let request = api::AccentPhrases {
+                                text: text.clone(),
+                                speaker: audio_item.styleId,
+                                is_kana: Some(true),
+                                core_version: None,
+                            };
+                            self.job = Some(ReadingJob {
+                                sent: accent_phrases.to_vec(),
+                                speaker: audio_item.styleId,
+                                job: Job::spawn(async move { request.call().await }),
+                            });
+                        }
+                        Err(e) => self.error = Some(e.to_string()),
+                    }
+                }
+            }
+            if self.job.is_some() {
+                ui.spinner();
+            }
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+        rt
+    }
+
+    /// receive accent phrases of edited kana.
+    fn poll(
+        &mut self,
+        audio_item: &AudioItem,
+        accent_phrases: &[AccentPhraseInProject],
+    ) -> Option<AudioQueryCommands> {
+        let result = match self.job.as_mut()?.job.try_recv() {
+            Err(TryRecvError::Empty) => return None,
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(TryRecvError::Closed) => Err(api::APIError::Unknown.to_string()),
+        };
+        let job = self.job.take()?;
+        let current = audio_item.styleId == job.speaker && accent_phrases == job.sent.as_slice();
+        match result {
+            Ok(next) if current => {
+                self.editing = None;
+                Some(AudioQueryCommands::UpdateAccentPhrases {
+                    new_text: audio_item.text.clone(),
+                    prev_text: audio_item.text.clone(),
+                    accent_phrases: carry_over_kana_phrases(accent_phrases, &next),
+                })
+            }
+            Ok(_) => {
+                self.editing = None;
+                log::debug!("discard stale accent phrases for {}", self.line);
+                None
+            }
+            Err(e) => {
+                log::error!("accent phrases request for {} failed {}", self.line, e);
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+}
@@ -1,66 +1,123 @@
-use crate::commands::AudioQueryEditCommand;
+use crate::chara_change_button::STYLE_ID_AND_CHARA_TABLE;
+use crate::commands::{AudioQueryEditCommand, MorphingCommand, UpspeakCommand};
+use crate::project::{AudioItem, MorphingInfo};
 
+use eframe::egui;
 use eframe::egui::Ui;
+use std::ops::RangeInclusive;
 use voice_vox_api::api_schema::AudioQueryInProject;
 
+type Control = (
+    &'static str,
+    fn(f32) -> AudioQueryEditCommand,
+    RangeInclusive<f32>,
+);
+
+const CONTROLS: [Control; 6] = [
+    ("話速", AudioQueryEditCommand::SpeedScale, 0.50..=2.0),
+    ("音高", AudioQueryEditCommand::PitchScale, -0.15..=0.15),
+    ("抑揚", AudioQueryEditCommand::IntonationScale, 0.0..=2.0),
+    ("音量", AudioQueryEditCommand::VolumeScale, 0.0..=2.0),
+    (
+        "開始無音",
+        AudioQueryEditCommand::PrePhonemeLength,
+        0.0..=1.5,
+    ),
+    (
+        "終了無音",
+        AudioQueryEditCommand::PostPhonemeLength,
+        0.0..=1.5,
+    ),
+];
+
+/// render sliders for selected lines.
+///
+/// if values differ between lines, value is shown as indeterminate.
+///
+/// returned command holds new value of the parameter, not a difference.
+/// convert it with [AudioQueryEditCommand::diff_from] for each line.
 pub fn render_synthesis_control(
-    aq_prev: &AudioQueryInProject,
+    queries: &[&AudioQueryInProject],
     ui: &mut Ui,
 ) -> Option<AudioQueryEditCommand> {
     let mut rt = None;
-    let mut aq = aq_prev.clone();
+    let first = queries.first()?;
     ui.vertical(|ui| {
-        ui.label(format!("話速 {}", aq.speedScale));
-        let slider = eframe::egui::Slider::new(&mut aq.speedScale, 0.50..=2.0).show_value(false);
-        if ui.add(slider).drag_released() {
-            rt = Some(AudioQueryEditCommand::SpeedScale(
-                aq.speedScale - aq_prev.speedScale,
-            ));
-            return;
-        }
-        ui.label(format!("音高 {}", aq.pitchScale));
-        let slider = eframe::egui::Slider::new(&mut aq.pitchScale, -0.15..=0.15).show_value(false);
-        if ui.add(slider).drag_released() {
-            rt = Some(AudioQueryEditCommand::PitchScale(
-                aq.pitchScale - aq_prev.pitchScale,
-            ));
-            return;
-        }
-        ui.label(format!("抑揚 {}", aq.intonationScale));
-        let slider =
-            eframe::egui::Slider::new(&mut aq.intonationScale, 0.0..=2.0).show_value(false);
-        if ui.add(slider).drag_released() {
-            rt = Some(AudioQueryEditCommand::IntonationScale(
-                aq.intonationScale - aq_prev.intonationScale,
-            ));
-            return;
+        for (label, edit, range) in CONTROLS {
+            let mut value = edit(0.0).value_of(first);
+            let mixed = queries
+                .iter()
+                .any(|aq| (edit(0.0).value_of(aq) - value).abs() > f32::EPSILON);
+            if mixed {
+                ui.label(format!("{} -", label));
+            } else {
+                ui.label(format!("{} {}", label, value));
+            }
+            let slider = eframe::egui::Slider::new(&mut value, range).show_value(false);
+            if ui.add(slider).drag_released() {
+                rt = Some(edit(value));
+            }
         }
-        ui.label(format!("音量 {}", aq.volumeScale));
-        let slider = eframe::egui::Slider::new(&mut aq.volumeScale, 0.0..=2.0).show_value(false);
-        if ui.add(slider).drag_released() {
-            rt = Some(AudioQueryEditCommand::VolumeScale(
-                aq.volumeScale - aq_prev.volumeScale,
-            ));
-            return;
-        }
-        ui.label(format!("開始無音 {}", aq.prePhonemeLength));
-        let slider =
-            eframe::egui::Slider::new(&mut aq.prePhonemeLength, 0.0..=1.5).show_value(false);
-        if ui.add(slider).drag_released() {
-            rt = Some(AudioQueryEditCommand::PrePhonemeLength(
-                aq.prePhonemeLength - aq_prev.prePhonemeLength,
-            ));
-            return;
+    });
+    rt
+}
+
+/// render morphing target and rate of a line.
+pub fn render_morphing_control(item: &AudioItem, ui: &mut Ui) -> Option<MorphingCommand> {
+    let table = STYLE_ID_AND_CHARA_TABLE.get()?;
+    let style_name = |id: &i32| {
+        table
+            .get(id)
+            .map(|(chara, style)| format!("{}({})", chara, style))
+            .unwrap_or_default()
+    };
+    let prev = item.morphingInfo.clone();
+    let mut next = prev.clone();
+    ui.vertical(|ui| {
+        let mut enabled = next.is_some();
+        if ui.checkbox(&mut enabled, "モーフィング").changed() {
+            next = if enabled {
+                Some(MorphingInfo {
+                    rate: 0.5,
+                    targetStyleId: item.styleId,
+                })
+            } else {
+                None
+            };
         }
-        ui.label(format!("終了無音 {}", aq.postPhonemeLength));
-        let slider =
-            eframe::egui::Slider::new(&mut aq.postPhonemeLength, 0.0..=1.5).show_value(false);
-        if ui.add(slider).drag_released() {
-            rt = Some(AudioQueryEditCommand::PostPhonemeLength(
-                aq.postPhonemeLength - aq_prev.postPhonemeLength,
-            ));
-            return;
+        if let Some(info) = &mut next {
+            egui::ComboBox::from_id_source("morphing_target")
+                .selected_text(style_name(&info.targetStyleId))
+                .show_ui(ui, |ui| {
+                    for id in table.keys() {
+                        ui.selectable_value(&mut info.targetStyleId, *id, style_name(id));
+                    }
+                });
+            ui.label(format!("割合 {:.2}", info.rate));
+            let mut rate = info.rate;
+            let slider = egui::Slider::new(&mut rate, 0.0..=1.0).show_value(false);
+            if ui.add(slider).drag_released() {
+                info.rate = rate;
+            }
         }
     });
-    rt
+    if next != prev {
+        Some(MorphingCommand { prev, next })
+    } else {
+        None
+    }
+}
+
+/// render whether end of questions is raised on synthesis.
+pub fn render_upspeak_control(item: &AudioItem, ui: &mut Ui) -> Option<UpspeakCommand> {
+    let prev = item.interrogativeUpspeak;
+    let mut upspeak = prev.unwrap_or(true);
+    if ui.checkbox(&mut upspeak, "疑問文を自動調整").changed() {
+        Some(UpspeakCommand {
+            prev,
+            next: Some(upspeak),
+        })
+    } else {
+        None
+    }
 }
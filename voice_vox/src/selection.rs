@@ -0,0 +1,146 @@
+use eframe::egui::Modifiers;
+use std::collections::HashSet;
+
+/// lines selected in central panel.
+#[derive(Default)]
+pub struct Selection {
+    /// start point of range selection.
+    anchor: Option<String>,
+    lines: HashSet<String>,
+}
+
+impl Selection {
+    /// select only `uuid`.
+    pub fn select(&mut self, uuid: &str) {
+        self.lines.clear();
+        self.lines.insert(uuid.to_owned());
+        self.anchor = Some(uuid.to_owned());
+    }
+
+    /// update selection by click on `uuid`.
+    /// * Shift : select range from anchor.
+    /// * Ctrl : toggle.
+    ///
+    /// `order` is line order of project.
+    pub fn click(&mut self, uuid: &str, order: &[String], modifiers: Modifiers) {
+        if modifiers.shift {
+            let position = |uuid: &str| order.iter().position(|line| line == uuid);
+            if let Some((anchor, clicked)) = self
+                .anchor
+                .as_deref()
+                .and_then(position)
+                .zip(position(uuid))
+            {
+                let range = anchor.min(clicked)..=anchor.max(clicked);
+                self.lines = order[range].iter().cloned().collect();
+                return;
+            }
+        } else if modifiers.command {
+            if !self.lines.remove(uuid) {
+                self.lines.insert(uuid.to_owned());
+            }
+            self.anchor = Some(uuid.to_owned());
+            return;
+        }
+        self.select(uuid);
+    }
+
+    pub fn select_all(&mut self, order: &[String]) {
+        self.lines = order.iter().cloned().collect();
+    }
+
+    pub fn contains(&self, uuid: &str) -> bool {
+        self.lines.contains(uuid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// selected lines in project order.
+    pub fn ordered(&self, order: &[String]) -> Vec<String> {
+        order
+            .iter()
+            .filter(|line| self.lines.contains(*line))
+            .cloned()
+            .collect()
+    }
+
+    /// lines operation on `clicked` applies to. whole selection if `clicked` is selected.
+    pub fn targets(&self, clicked: &str, order: &[String]) -> Vec<String> {
+        if self.contains(clicked) {
+            self.ordered(order)
+        } else {
+            vec![clicked.to_owned()]
+        }
+    }
+
+    /// whether removing [Self::targets] leaves at least one line.
+    pub fn can_remove(&self, clicked: &str, order: &[String]) -> bool {
+        self.targets(clicked, order).len() < order.len()
+    }
+
+    /// forget removed lines.
+    pub fn retain(&mut self, order: &[String]) {
+        self.lines.retain(|line| order.contains(line));
+        if let Some(anchor) = &self.anchor {
+            if !order.contains(anchor) {
+                self.anchor = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Selection;
+    use eframe::egui::Modifiers;
+
+    #[test]
+    fn range_and_toggle() {
+        let order: Vec<String> = ["a", "b", "c", "d"].iter().map(|x| x.to_string()).collect();
+        let mut selection = Selection::default();
+        selection.click("b", &order, Modifiers::default());
+        selection.click(
+            "d",
+            &order,
+            Modifiers {
+                shift: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(selection.ordered(&order), vec!["b", "c", "d"]);
+        selection.click(
+            "c",
+            &order,
+            Modifiers {
+                command: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(selection.ordered(&order), vec!["b", "d"]);
+        selection.click("a", &order, Modifiers::default());
+        assert_eq!(selection.ordered(&order), vec!["a"]);
+    }
+
+    #[test]
+    fn removal_keeps_a_line() {
+        let order: Vec<String> = ["a", "b", "c"].iter().map(|x| x.to_string()).collect();
+        let mut selection = Selection::default();
+        selection.select_all(&order);
+        assert_eq!(selection.targets("b", &order), order);
+        assert!(!selection.can_remove("b", &order));
+        selection.click(
+            "c",
+            &order,
+            Modifiers {
+                command: true,
+                ..Default::default()
+            },
+        );
+        assert!(selection.can_remove("a", &order));
+        assert_eq!(selection.targets("c", &order), vec!["c"]);
+        assert!(selection.can_remove("c", &order));
+        assert!(!Selection::default().can_remove("a", &order[..1]));
+    }
+}
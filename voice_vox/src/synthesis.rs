@@ -0,0 +1,168 @@
+use crate::api;
+use crate::api::{APIError, Api};
+use crate::project::AudioItem;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use voice_vox_api::api_schema::AudioQuery;
+use voice_vox_api::cache::{CacheKey, CachedSynthesis, DiskCache};
+
+/// cleared once engine reports cancellable synthesis is disabled.
+static CANCELLABLE: AtomicBool = AtomicBool::new(true);
+
+/// size limit of on-disk cache.
+const DISK_CACHE_LIMIT: u64 = 512 * 1024 * 1024;
+
+/// on-disk cache and version of running engine.
+///
+/// not set if engine version or cache directory is unavailable.
+static DISK_CACHE: once_cell::race::OnceBox<(Arc<DiskCache>, String)> =
+    once_cell::race::OnceBox::new();
+
+/// toggled from settings menu.
+pub static USE_DISK_CACHE: AtomicBool = AtomicBool::new(true);
+
+pub(crate) async fn init_disk_cache() -> Option<()> {
+    let engine_version = api::Version.call().await.ok()??;
+    let dir = dirs::cache_dir()?.join("voice_vox_rust").join("synthesis");
+    let cache = DiskCache::new(dir, DISK_CACHE_LIMIT).await.ok()?;
+    DISK_CACHE
+        .set(Box::new((Arc::new(cache), engine_version)))
+        .ok()
+}
+
+/// wrap `synthesis` with on-disk cache. `synthesis` is returned back while cache is off.
+fn cached<T>(synthesis: T) -> Result<CachedSynthesis<T>, T> {
+    match DISK_CACHE.get() {
+        Some((cache, engine_version)) if USE_DISK_CACHE.load(Ordering::Relaxed) => {
+            Ok(CachedSynthesis {
+                cache: cache.clone(),
+                engine_version: engine_version.clone(),
+                synthesis,
+            })
+        }
+        _ => Err(synthesis),
+    }
+}
+
+async fn call_cached<T>(synthesis: T) -> Result<Vec<u8>, APIError>
+where
+    T: Api<Response = Result<Vec<u8>, APIError>> + CacheKey + Send + Sync,
+{
+    match cached(synthesis) {
+        Ok(cached) => cached.call().await,
+        Err(synthesis) => synthesis.call().await,
+    }
+}
+
+/// synthesize line into wav.
+///
+/// used by both playback and export so morphing setting of line is always applied.
+/// on-disk cache is consulted first.
+///
+/// `/cancellable_synthesis` is used if engine enables it, so dropping the future stops the engine.
+/// it always applies upspeak, so lines without it are sent to `/synthesis`.
+pub async fn synthesize(item: AudioItem) -> Result<Vec<u8>, APIError> {
+    let upspeak = upspeak(&item);
+    let audio_query: AudioQuery = item.query.ok_or(APIError::Unknown)?.into();
+    if let Some(morphing) = item.morphingInfo {
+        return call_cached(api::SynthesisMorphing {
+            base_speaker: item.styleId,
+            target_speaker: morphing.targetStyleId,
+            morph_rate: morphing.rate,
+            core_version: None,
+            audio_query,
+        })
+        .await;
+    }
+    if upspeak && CANCELLABLE.load(Ordering::Relaxed) {
+        match call_cached(api::CancellableSynthesis {
+            speaker: item.styleId,
+            core_version: None,
+            audio_query: audio_query.clone(),
+        })
+        .await
+        {
+            Err(APIError::NotEnabled(e)) => {
+                log::info!("fall back to uncancellable synthesis. {}", e.detail);
+                CANCELLABLE.store(false, Ordering::Relaxed);
+            }
+            result => return result,
+        }
+    }
+    call_cached(api::Synthesis {
+        speaker: item.styleId,
+        enable_interrogative_upspeak: Some(upspeak),
+        core_version: None,
+        audio_query,
+    })
+    .await
+}
+
+/// engine raises end of questions unless told otherwise.
+fn upspeak(item: &AudioItem) -> bool {
+    item.interrogativeUpspeak.unwrap_or(true)
+}
+
+/// synthesize lines into wavs in order.
+///
+/// lines without morphing are grouped by style and sent with [api::MultiSynthesis]
+/// to cut round trips. lines found in on-disk cache are not sent.
+/// `/multi_synthesis` always applies upspeak, so lines without it are synthesized one by one.
+pub async fn synthesize_all(items: Vec<AudioItem>) -> Result<Vec<Vec<u8>>, APIError> {
+    let mut groups: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+    for (i, item) in items.iter().enumerate() {
+        if item.morphingInfo.is_none() && item.query.is_some() && upspeak(item) {
+            groups.entry(item.styleId).or_default().push(i);
+        }
+    }
+    let mut waves: Vec<Option<Vec<u8>>> = vec![None; items.len()];
+    for (speaker, indices) in groups {
+        let mut misses = Vec::new();
+        for i in indices {
+            let request = cached(api::Synthesis {
+                speaker,
+                enable_interrogative_upspeak: Some(true),
+                core_version: None,
+                audio_query: items[i].query.clone().unwrap().into(),
+            });
+            if let Ok(cached) = &request {
+                if let Some(wav) = cached.lookup().await {
+                    waves[i] = Some(wav);
+                    continue;
+                }
+            }
+            misses.push((i, request));
+        }
+        if misses.is_empty() {
+            continue;
+        }
+        let synthesized = api::MultiSynthesis {
+            speaker,
+            core_version: None,
+            audio_query: misses
+                .iter()
+                .map(|(_, request)| match request {
+                    Ok(cached) => cached.synthesis.audio_query.clone(),
+                    Err(synthesis) => synthesis.audio_query.clone(),
+                })
+                .collect(),
+        }
+        .call()
+        .await?;
+        for ((i, request), wave) in misses.into_iter().zip(synthesized) {
+            if let Ok(cached) = request {
+                cached.store(&wave).await;
+            }
+            waves[i] = Some(wave);
+        }
+    }
+    let mut rt = Vec::with_capacity(items.len());
+    for (wave, item) in waves.into_iter().zip(items) {
+        rt.push(match wave {
+            Some(wave) => wave,
+            None => synthesize(item).await?,
+        });
+    }
+    Ok(rt)
+}
@@ -0,0 +1,329 @@
+use crate::api;
+use crate::api::Api;
+use crate::job::Job;
+use crate::project::AudioItem;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::Cursor;
+use tokio::sync::oneshot::error::TryRecvError;
+
+/// default byte budget of finished wavs.
+pub const DEFAULT_BUDGET: usize = 64 * 1024 * 1024;
+
+pub enum SynthesisState {
+    WaitingForSynthesis(Job<<crate::api::Synthesis as Api>::Response>),
+    Finished(Cursor<Vec<u8>>),
+    /// readable reason of failure.
+    Failed(String),
+}
+
+/// hash of everything which affects synthesized audio.
+///
+/// identical lines share audio, so undo reuses audio of earlier revision.
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
+pub struct SynthesisKey(u64);
+
+impl SynthesisKey {
+    pub fn of(item: &AudioItem) -> Option<Self> {
+        let query = item.query.as_ref()?;
+        let mut hasher = DefaultHasher::new();
+        item.styleId.hash(&mut hasher);
+        item.interrogativeUpspeak.unwrap_or(true).hash(&mut hasher);
+        if let Some(morphing) = &item.morphingInfo {
+            morphing.targetStyleId.hash(&mut hasher);
+            morphing.rate.to_bits().hash(&mut hasher);
+        }
+        serde_json::to_writer(HashWriter(&mut hasher), query).ok()?;
+        Some(Self(hasher.finish()))
+    }
+}
+
+/// feed serialized bytes into hasher.
+struct HashWriter<'a, H: Hasher>(&'a mut H);
+
+impl<H: Hasher> std::io::Write for HashWriter<'_, H> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Entry {
+    state: SynthesisState,
+    /// lines which requested this audio.
+    lines: HashSet<String>,
+    /// value of [SynthesisCache::clock] at last access.
+    last_used: u64,
+}
+
+impl Entry {
+    fn bytes(&self) -> usize {
+        match &self.state {
+            SynthesisState::Finished(wav) => wav.get_ref().len(),
+            _ => 0,
+        }
+    }
+}
+
+/// least recently used cache of synthesized audio bounded by total wav size.
+pub struct SynthesisCache {
+    entries: HashMap<SynthesisKey, Entry>,
+    clock: u64,
+    bytes: usize,
+    budget: usize,
+}
+
+impl SynthesisCache {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            clock: 0,
+            bytes: 0,
+            budget,
+        }
+    }
+
+    pub fn get(&mut self, key: &SynthesisKey) -> Option<&SynthesisState> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.clock;
+        Some(&entry.state)
+    }
+
+    /// unlike [Self::get], recency is not updated.
+    pub fn peek(&self, key: &SynthesisKey) -> Option<&SynthesisState> {
+        self.entries.get(key).map(|entry| &entry.state)
+    }
+
+    /// number of synthesis requests in flight.
+    pub fn in_flight(&self) -> usize {
+        self.entries
+            .values()
+            .filter(|entry| matches!(entry.state, SynthesisState::WaitingForSynthesis(_)))
+            .count()
+    }
+
+    /// true while less than half of budget is used.
+    ///
+    /// background synthesis stops beyond this so that it does not evict audio in use.
+    pub fn has_room(&self) -> bool {
+        self.bytes < self.budget / 2
+    }
+
+    /// register synthesis of `item` on `line` unless it is cached or in flight.
+    pub fn request(&mut self, line: &str, item: &AudioItem) {
+        let key = if let Some(key) = SynthesisKey::of(item) {
+            key
+        } else {
+            return;
+        };
+        self.clock += 1;
+        let mut lines = HashSet::from([line.to_owned()]);
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.last_used = self.clock;
+            entry.lines.insert(line.to_owned());
+            if !matches!(entry.state, SynthesisState::Failed(_)) {
+                return;
+            }
+            // retry still belongs to other lines sharing the audio.
+            lines = std::mem::take(&mut entry.lines);
+        }
+        log::debug!("send synthesis request for {} as {:?}", line, key);
+        let job = Job::spawn(crate::synthesis::synthesize(item.clone()));
+        self.entries.insert(
+            key,
+            Entry {
+                state: SynthesisState::WaitingForSynthesis(job),
+                lines,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// receive finished synthesis and evict old entries beyond budget.
+    pub fn poll(&mut self) {
+        for entry in self.entries.values_mut() {
+            if let SynthesisState::WaitingForSynthesis(job) = &mut entry.state {
+                match job.try_recv() {
+                    Ok(Ok(wav)) => {
+                        self.bytes += wav.len();
+                        entry.state = SynthesisState::Finished(Cursor::new(wav));
+                    }
+                    Ok(Err(e)) => {
+                        log::error!("synthesis failed {:?}", e);
+                        entry.state = SynthesisState::Failed(e.to_string());
+                    }
+                    Err(TryRecvError::Closed) => {
+                        entry.state = SynthesisState::Failed(api::APIError::Unknown.to_string());
+                    }
+                    Err(TryRecvError::Empty) => {}
+                }
+            }
+        }
+        self.evict();
+    }
+
+    /// drop entries which no longer belong to any line.
+    ///
+    /// * `current` : current key of each line in project.
+    ///
+    /// requests and failures of outdated revisions are dropped.
+    /// finished audio of outdated revisions is kept for undo until budget is exceeded,
+    /// but audio of removed lines is dropped.
+    pub fn retain_lines(&mut self, current: &HashMap<&str, SynthesisKey>) {
+        let mut freed = 0;
+        self.entries.retain(|key, entry| {
+            entry
+                .lines
+                .retain(|line| current.contains_key(line.as_str()));
+            let in_use = entry
+                .lines
+                .iter()
+                .any(|line| current.get(line.as_str()) == Some(key));
+            let keep = match entry.state {
+                SynthesisState::WaitingForSynthesis(_) | SynthesisState::Failed(_) => in_use,
+                SynthesisState::Finished(_) => !entry.lines.is_empty(),
+            };
+            if !keep {
+                freed += entry.bytes();
+            }
+            keep
+        });
+        self.bytes -= freed;
+    }
+
+    /// abort synthesis in flight.
+    pub fn cancel_waiting(&mut self) {
+        self.entries
+            .retain(|_, entry| !matches!(entry.state, SynthesisState::WaitingForSynthesis(_)));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    /// drop least recently used audio until total size fits in budget.
+    fn evict(&mut self) {
+        while self.bytes > self.budget {
+            let oldest = self
+                .entries
+                .iter()
+                .filter(|(_, entry)| entry.bytes() > 0)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+            if let Some(entry) = oldest.and_then(|key| self.entries.remove(&key)) {
+                log::debug!("evict {} bytes of synthesized audio", entry.bytes());
+                self.bytes -= entry.bytes();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+impl Default for SynthesisCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUDGET)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Entry, SynthesisCache, SynthesisKey, SynthesisState};
+    use crate::project::test::sample_project;
+    use std::collections::{HashMap, HashSet};
+    use std::io::Cursor;
+
+    fn finished(cache: &mut SynthesisCache, key: u64, line: &str, bytes: usize) {
+        cache.clock += 1;
+        cache.bytes += bytes;
+        cache.entries.insert(
+            SynthesisKey(key),
+            Entry {
+                state: SynthesisState::Finished(Cursor::new(vec![0; bytes])),
+                lines: HashSet::from([line.to_owned()]),
+                last_used: cache.clock,
+            },
+        );
+        cache.evict();
+    }
+
+    #[test]
+    fn key_follows_content() {
+        let project = sample_project();
+        let mut item = project.audioItems["line"].clone();
+        let key = SynthesisKey::of(&item);
+        item.styleId = 3;
+        assert_ne!(SynthesisKey::of(&item), key);
+        item.styleId = 2;
+        assert_eq!(SynthesisKey::of(&item), key);
+        item.interrogativeUpspeak = Some(false);
+        assert_ne!(SynthesisKey::of(&item), key);
+        // engine default.
+        item.interrogativeUpspeak = Some(true);
+        assert_eq!(SynthesisKey::of(&item), key);
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let mut cache = SynthesisCache::new(10);
+        finished(&mut cache, 0, "a", 4);
+        finished(&mut cache, 1, "a", 4);
+        cache.get(&SynthesisKey(0));
+        finished(&mut cache, 2, "b", 4);
+        assert!(cache.get(&SynthesisKey(1)).is_none());
+        assert!(cache.get(&SynthesisKey(0)).is_some());
+        assert_eq!(cache.bytes, 8);
+
+        cache.retain_lines(&HashMap::from([("b", SynthesisKey(2))]));
+        assert!(cache.get(&SynthesisKey(0)).is_none());
+        assert_eq!(cache.bytes, 4);
+    }
+
+    #[test]
+    fn drop_failure_of_outdated_revision() {
+        let mut cache = SynthesisCache::default();
+        cache.entries.insert(
+            SynthesisKey(0),
+            Entry {
+                state: SynthesisState::Failed("error".to_owned()),
+                lines: HashSet::from(["a".to_owned()]),
+                last_used: 0,
+            },
+        );
+        cache.retain_lines(&HashMap::from([("a", SynthesisKey(0))]));
+        assert!(cache.peek(&SynthesisKey(0)).is_some());
+        cache.retain_lines(&HashMap::from([("a", SynthesisKey(1))]));
+        assert!(cache.peek(&SynthesisKey(0)).is_none());
+    }
+
+    #[tokio::test]
+    async fn retry_keeps_lines_sharing_audio() {
+        let project = sample_project();
+        let item = &project.audioItems["line"];
+        let key = SynthesisKey::of(item).unwrap();
+        let mut cache = SynthesisCache::default();
+        cache.entries.insert(
+            key,
+            Entry {
+                state: SynthesisState::Failed("error".to_owned()),
+                lines: HashSet::from(["a".to_owned(), "b".to_owned()]),
+                last_used: 0,
+            },
+        );
+        cache.request("a", item);
+        assert!(matches!(
+            cache.peek(&key),
+            Some(SynthesisState::WaitingForSynthesis(_))
+        ));
+        // request is kept while "b" still uses the audio.
+        cache.retain_lines(&HashMap::from([("b", key)]));
+        assert!(cache.peek(&key).is_some());
+    }
+}
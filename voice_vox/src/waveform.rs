@@ -0,0 +1,172 @@
+use crate::pitch_editor::PITCH_RANGE;
+use crate::synthesis_cache::SynthesisKey;
+use eframe::egui::{pos2, vec2, Align2, Color32, FontId, Sense, Shape, Stroke, Ui};
+use rodio::Source;
+use std::io::Cursor;
+use voice_vox_api::api_schema::AudioQueryInProject;
+
+/// horizontal scale of view.
+const PIXELS_PER_SECOND: f32 = 400.0;
+/// resolution of waveform.
+const PEAKS_PER_SECOND: u32 = 200;
+const HEIGHT: f32 = 96.0;
+
+/// position of mora in synthesized audio.
+#[derive(Debug, PartialEq)]
+pub struct MoraSpan {
+    pub text: String,
+    /// seconds from start of audio.
+    pub start: f32,
+    pub end: f32,
+    /// 0 if unvoiced.
+    pub pitch: f32,
+}
+
+/// place moras on time axis the way engine does.
+///
+/// every length including pre and post phoneme is divided by speed scale.
+pub fn mora_spans(query: &AudioQueryInProject) -> Vec<MoraSpan> {
+    let speed = query.speedScale.max(f32::EPSILON);
+    let mut time = query.prePhonemeLength / speed;
+    let mut spans = Vec::new();
+    let moras = query
+        .accentPhrases
+        .iter()
+        .flat_map(|ap| ap.moras.iter().chain(ap.pause_mora.iter()));
+    for mora in moras {
+        let length = (mora.consonantLength.unwrap_or(0.0) + mora.vowelLength) / speed;
+        spans.push(MoraSpan {
+            text: mora.text.clone(),
+            start: time,
+            end: time + length,
+            pitch: mora.pitch,
+        });
+        time += length;
+    }
+    spans
+}
+
+/// decoded audio of a line with its mora timing.
+pub struct Waveform {
+    /// audio this view was decoded from.
+    pub key: SynthesisKey,
+    /// absolute peak of each slice, in 0..=1.
+    peaks: Vec<f32>,
+    duration: f32,
+    spans: Vec<MoraSpan>,
+}
+
+impl Waveform {
+    pub fn new(key: SynthesisKey, wav: &[u8], query: &AudioQueryInProject) -> Option<Self> {
+        let decoder = rodio::Decoder::new_wav(Cursor::new(wav.to_vec())).ok()?;
+        let slice = (decoder.sample_rate() / PEAKS_PER_SECOND).max(1) as usize
+            * decoder.channels() as usize;
+        let samples: Vec<i16> = decoder.collect();
+        let peaks: Vec<f32> = samples
+            .chunks(slice)
+            .map(|chunk| {
+                let peak = chunk.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+                peak as f32 / i16::MAX as f32
+            })
+            .collect();
+        let duration = peaks.len() as f32 / PEAKS_PER_SECOND as f32;
+        Some(Self {
+            key,
+            peaks,
+            duration,
+            spans: mora_spans(query),
+        })
+    }
+
+    /// draw waveform, mora boundaries and pitch curve.
+    ///
+    /// * `playhead` : seconds from start of playback.
+    pub fn ui(&self, ui: &mut Ui, playhead: Option<f32>) {
+        let width = (self.duration * PIXELS_PER_SECOND).max(ui.available_width());
+        let (response, painter) = ui.allocate_painter(vec2(width, HEIGHT), Sense::hover());
+        let rect = response.rect;
+        let x = |time: f32| rect.left() + time * PIXELS_PER_SECOND;
+        let visuals = ui.visuals();
+        painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+
+        let center = rect.center().y;
+        let wave = Stroke::new(1.0, visuals.weak_text_color());
+        for (i, peak) in self.peaks.iter().enumerate() {
+            let left = x(i as f32 / PEAKS_PER_SECOND as f32);
+            let half = peak * HEIGHT / 2.0;
+            painter.line_segment([pos2(left, center - half), pos2(left, center + half)], wave);
+        }
+
+        let boundary = Stroke::new(1.0, visuals.widgets.noninteractive.bg_stroke.color);
+        let font = FontId::proportional(12.0);
+        for span in &self.spans {
+            painter.line_segment(
+                [
+                    pos2(x(span.start), rect.top()),
+                    pos2(x(span.start), rect.bottom()),
+                ],
+                boundary,
+            );
+            painter.text(
+                pos2((x(span.start) + x(span.end)) / 2.0, rect.bottom()),
+                Align2::CENTER_BOTTOM,
+                &span.text,
+                font.clone(),
+                visuals.text_color(),
+            );
+        }
+
+        // curve is broken at unvoiced moras.
+        let pitch_y = |pitch: f32| {
+            let (low, high) = (*PITCH_RANGE.start(), *PITCH_RANGE.end());
+            rect.bottom() - (pitch - low) / (high - low) * rect.height()
+        };
+        let curve = Stroke::new(2.0, Color32::LIGHT_BLUE);
+        let mut points = Vec::new();
+        for span in &self.spans {
+            if span.pitch > 0.0 {
+                points.push(pos2(
+                    (x(span.start) + x(span.end)) / 2.0,
+                    pitch_y(span.pitch),
+                ));
+            } else if !points.is_empty() {
+                painter.add(Shape::line(std::mem::take(&mut points), curve));
+            }
+        }
+        painter.add(Shape::line(points, curve));
+
+        if let Some(time) = playhead.filter(|time| *time <= self.duration) {
+            painter.line_segment(
+                [pos2(x(time), rect.top()), pos2(x(time), rect.bottom())],
+                Stroke::new(2.0, Color32::RED),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::mora_spans;
+    use crate::project::test::sample_project;
+    use voice_vox_api::api_schema::MoraInProject;
+
+    #[test]
+    fn spans_follow_lengths_and_speed() {
+        let mut query = sample_project().audioItems["line"].query.clone().unwrap();
+        query.accentPhrases[0].pause_mora = Some(MoraInProject {
+            text: "、".to_string(),
+            consonant: None,
+            consonantLength: None,
+            vowel: "pau".to_string(),
+            vowelLength: 0.3,
+            pitch: 0.0,
+        });
+        query.speedScale = 2.0;
+        let spans = mora_spans(&query);
+        assert_eq!(spans.len(), 2);
+        assert!((spans[0].start - 0.05).abs() < 1e-6);
+        assert!((spans[0].end - 0.1).abs() < 1e-6);
+        assert!((spans[1].end - 0.25).abs() < 1e-6);
+        assert_eq!(spans[1].pitch, 0.0);
+    }
+}
@@ -2,7 +2,9 @@
 //!
 //!
 
-use crate::api_schema::{AccentPhrase, AccentPhrasesResponse, HttpValidationError, KanaParseError};
+use crate::api_schema::{
+    AccentPhrase, HttpError, HttpValidationError, KanaParseError, KanaParseErrorResponse,
+};
 use async_trait::async_trait;
 use once_cell::race::OnceBox;
 use reqwest::{Error, StatusCode};
@@ -117,6 +119,17 @@ impl From<reqwest::Error> for AccentPhrasesErrors {
     }
 }
 
+impl std::fmt::Display for AccentPhrasesErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccentPhrasesErrors::KanaParseError(e) => write!(f, "{}", e.text),
+            AccentPhrasesErrors::ApiError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for AccentPhrasesErrors {}
+
 impl From<reqwest::StatusCode> for AccentPhrasesErrors {
     fn from(e: reqwest::StatusCode) -> Self {
         AccentPhrasesErrors::ApiError(e.into())
@@ -125,22 +138,22 @@ impl From<reqwest::StatusCode> for AccentPhrasesErrors {
 
 #[async_trait]
 impl Api for AccentPhrases {
-    type Response = Result<AccentPhrasesResponse, AccentPhrasesErrors>;
+    type Response = Result<Vec<AccentPhrase>, AccentPhrasesErrors>;
 
     async fn call(&self) -> Self::Response {
         let request = client()
-            .post("http://localhost:50021/audio_query")
+            .post("http://localhost:50021/accent_phrases")
             .query(&[("speaker", self.speaker)])
             .add_core_version(&self.core_version)
             .query(&[("is_kana", self.is_kana.unwrap_or(false))])
             .query(&[("text", &self.text)])
             .build()?;
-        let res = client().execute(request).await.unwrap();
+        let res = client().execute(request).await?;
         match res.status() {
             StatusCode::OK => Ok(res.json::<_>().await?),
-            StatusCode::BAD_REQUEST => {
-                Err(AccentPhrasesErrors::KanaParseError(res.json::<_>().await?))
-            }
+            StatusCode::BAD_REQUEST => Err(AccentPhrasesErrors::KanaParseError(
+                res.json::<KanaParseErrorResponse>().await?.detail,
+            )),
             StatusCode::UNPROCESSABLE_ENTITY => Err(AccentPhrasesErrors::ApiError(
                 APIError::Validation(res.json::<_>().await?),
             )),
@@ -269,6 +282,9 @@ impl Api for Synthesis {
 }
 
 /// # 音声合成する（キャンセル可能）
+///
+/// リクエストを途中で破棄すると接続が切れ,エンジンは合成を中断します.
+/// エンジンが`--enable_cancellable_synthesis`で起動されていない場合は[APIError::NotEnabled]を返します.
 pub struct CancellableSynthesis {
     // in query
     pub speaker: i32,
@@ -287,9 +303,10 @@ impl Api for CancellableSynthesis {
             .add_core_version(&self.core_version)
             .json(&self.audio_query)
             .build()?;
-        let res = client().execute(request).await.unwrap();
+        let res = client().execute(request).await?;
         match res.status() {
             StatusCode::OK => Ok(res.bytes().await.unwrap_or_default().to_vec()),
+            StatusCode::NOT_FOUND => Err(APIError::NotEnabled(res.json::<_>().await?)),
             StatusCode::UNPROCESSABLE_ENTITY => Err(APIError::Validation(res.json::<_>().await?)),
             x => Err(x.into()),
         }
@@ -299,6 +316,7 @@ impl Api for CancellableSynthesis {
 /// # まとめて音声合成する
 ///
 /// 複数のwavがzipでまとめられて返されます.
+/// 展開したwavをクエリの順番で返します.
 pub struct MultiSynthesis {
     // in query
     pub speaker: i32,
@@ -309,7 +327,7 @@ pub struct MultiSynthesis {
 
 #[async_trait]
 impl Api for MultiSynthesis {
-    type Response = Result<Vec<u8>, APIError>;
+    type Response = Result<Vec<Vec<u8>>, APIError>;
 
     async fn call(&self) -> Self::Response {
         let request = client()
@@ -317,17 +335,42 @@ impl Api for MultiSynthesis {
             .query(&[("speaker", self.speaker)])
             .add_core_version(&self.core_version)
             .json(&self.audio_query)
-            .build()
-            .unwrap();
-        let res = client().execute(request).await.unwrap();
+            .build()?;
+        let res = client().execute(request).await?;
         match res.status() {
-            StatusCode::OK => Ok(res.bytes().await.unwrap_or_default().to_vec()),
+            StatusCode::OK => {
+                let waves = unzip_waves(&res.bytes().await?)?;
+                if waves.len() == self.audio_query.len() {
+                    Ok(waves)
+                } else {
+                    Err(APIError::MalformedArchive(format!(
+                        "expected {} wav files but found {}",
+                        self.audio_query.len(),
+                        waves.len()
+                    )))
+                }
+            }
             StatusCode::UNPROCESSABLE_ENTITY => Err(APIError::Validation(res.json::<_>().await?)),
             x => Err(x.into()),
         }
     }
 }
 
+/// zipに格納された順番でwavを取り出す.
+pub fn unzip_waves(archive: &[u8]) -> Result<Vec<Vec<u8>>, APIError> {
+    let malformed = |e: zip::result::ZipError| APIError::MalformedArchive(e.to_string());
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(archive)).map_err(malformed)?;
+    let mut waves = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(malformed)?;
+        let mut wave = Vec::with_capacity(file.size() as usize);
+        std::io::Read::read_to_end(&mut file, &mut wave)
+            .map_err(|e| APIError::MalformedArchive(e.to_string()))?;
+        waves.push(wave);
+    }
+    Ok(waves)
+}
+
 /// # 2人の話者でモーフィングした音声を合成する
 ///
 /// 指定された2人の話者で音声を合成、指定した割合でモーフィングした音声を得ます。 モーフィングの割合はmorph_rateで指定でき、0.0でベースの話者、1.0でターゲットの話者に近づきます。
@@ -360,6 +403,8 @@ impl Api for SynthesisMorphing {
         let res = client().execute(request).await.unwrap();
         match res.status() {
             StatusCode::OK => Ok(res.bytes().await.unwrap_or_default().to_vec()),
+            // unsupported speaker pair.
+            StatusCode::BAD_REQUEST => Err(APIError::BadRequest(res.json::<_>().await?)),
             StatusCode::UNPROCESSABLE_ENTITY => Err(APIError::Validation(res.json::<_>().await?)),
             x => Err(x.into()),
         }
@@ -547,11 +592,36 @@ impl AddCoreVersion for reqwest::RequestBuilder {
 #[derive(Debug)]
 pub enum APIError {
     Validation(HttpValidationError),
+    /// request is refused by engine with reason.
+    BadRequest(HttpError),
+    /// zip returned from engine could not be read.
+    MalformedArchive(String),
+    /// experimental endpoint is disabled on engine.
+    NotEnabled(HttpError),
     Io(std::io::Error),
     Reqwest(reqwest::Error),
     Unknown,
 }
 
+impl std::fmt::Display for APIError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            APIError::Validation(e) => {
+                let messages: Vec<_> = e.detail.iter().map(|d| d.msg.as_str()).collect();
+                write!(f, "invalid request: {}", messages.join(", "))
+            }
+            APIError::BadRequest(e) => write!(f, "{}", e.detail),
+            APIError::MalformedArchive(e) => write!(f, "malformed archive: {}", e),
+            APIError::NotEnabled(e) => write!(f, "{}", e.detail),
+            APIError::Io(e) => write!(f, "{}", e),
+            APIError::Reqwest(e) => write!(f, "{}", e),
+            APIError::Unknown => write!(f, "unknown error"),
+        }
+    }
+}
+
+impl std::error::Error for APIError {}
+
 impl From<reqwest::Error> for APIError {
     fn from(e: Error) -> Self {
         APIError::Reqwest(e)
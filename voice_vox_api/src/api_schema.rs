@@ -196,6 +196,12 @@ pub struct HttpValidationError {
     pub detail: Vec<ValidationError>,
 }
 
+/// error body of request which engine refused.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct HttpError {
+    pub detail: String,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ValidationError {
     ///Location
@@ -208,15 +214,17 @@ pub struct ValidationError {
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct AccentPhrasesResponse {
-    pub accent_phrases: Vec<AccentPhrase>,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
 pub struct KanaParseError {
+    /// readable message.
     pub text: String,
     pub error_name: String,
-    pub error_args: String,
+    pub error_args: serde_json::Value,
+}
+
+/// error body of `/accent_phrases` with `is_kana`.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct KanaParseErrorResponse {
+    pub detail: KanaParseError,
 }
 
 #[allow(non_snake_case, unused_variables)]
@@ -0,0 +1,260 @@
+//! on-disk cache of synthesized wav shared across sessions.
+//!
+
+use crate::api::{APIError, Api, CancellableSynthesis, CoreVersion, Synthesis, SynthesisMorphing};
+use crate::api_schema::AudioQuery;
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// cached files in memory. directory is read only when cache is opened.
+#[derive(Default)]
+struct Index {
+    /// incremented on every access. file with smallest tick is least recently used.
+    clock: u64,
+    /// key to (last access tick, size).
+    files: HashMap<String, (u64, u64)>,
+    total: u64,
+}
+
+impl Index {
+    fn touch(&mut self, key: &str, len: u64) {
+        self.clock += 1;
+        if let Some((_, prev_len)) = self.files.insert(key.to_owned(), (self.clock, len)) {
+            self.total -= prev_len;
+        }
+        self.total += len;
+    }
+
+    /// take least recently used keys out until total size fits in `max_bytes`.
+    fn evict(&mut self, max_bytes: u64) -> Vec<String> {
+        let mut order: Vec<_> = self
+            .files
+            .iter()
+            .map(|(key, (tick, _))| (*tick, key.clone()))
+            .collect();
+        order.sort();
+        let mut evicted = vec![];
+        for (_, key) in order {
+            if self.total <= max_bytes {
+                break;
+            }
+            if let Some((_, len)) = self.files.remove(&key) {
+                self.total -= len;
+                evicted.push(key);
+            }
+        }
+        evicted
+    }
+}
+
+/// wav files named by hash of synthesis request.
+///
+/// least recently used files are removed when total size exceeds `max_bytes`.
+/// access order is kept in memory and restored from modified time of files on open.
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<Index>,
+}
+
+impl DiskCache {
+    /// open cache directory. directory is created if missing.
+    pub async fn new(dir: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        tokio::fs::create_dir_all(&dir).await?;
+        let mut files = Vec::new();
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            let path = entry.path();
+            let key = match path.file_stem().and_then(OsStr::to_str) {
+                Some(key) if path.extension() == Some(OsStr::new("wav")) => key.to_owned(),
+                _ => continue,
+            };
+            if metadata.is_file() {
+                files.push((metadata.modified()?, key, metadata.len()));
+            }
+        }
+        files.sort();
+        let mut index = Index::default();
+        for (_, key, len) in files {
+            index.touch(&key, len);
+        }
+        let cache = Self {
+            dir,
+            max_bytes,
+            index: Mutex::new(index),
+        };
+        cache.evict().await?;
+        Ok(cache)
+    }
+
+    /// stable key of synthesis request.
+    ///
+    /// engine version is included so that audio of old engine is not reused after update.
+    /// `morphing` is target speaker and rate of [crate::api::SynthesisMorphing].
+    pub fn key(
+        engine_version: &str,
+        core_version: &CoreVersion,
+        speaker: i32,
+        enable_interrogative_upspeak: Option<bool>,
+        morphing: Option<(i32, f64)>,
+        audio_query: &AudioQuery,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(engine_version.as_bytes());
+        hasher.update([0]);
+        hasher.update(core_version.as_deref().unwrap_or_default().as_bytes());
+        hasher.update([0]);
+        hasher.update(speaker.to_le_bytes());
+        hasher.update([enable_interrogative_upspeak.map_or(2, u8::from)]);
+        if let Some((target_speaker, morph_rate)) = morphing {
+            hasher.update([1]);
+            hasher.update(target_speaker.to_le_bytes());
+            hasher.update(morph_rate.to_le_bytes());
+        } else {
+            hasher.update([0]);
+        }
+        hasher.update(serde_json::to_vec(audio_query).unwrap_or_default());
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.wav", key))
+    }
+
+    /// read cached wav and mark it as recently used.
+    pub async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let path = self.path(key);
+        let wav = tokio::fs::read(&path).await.ok()?;
+        self.index.lock().unwrap().touch(key, wav.len() as u64);
+        // keep order for next session.
+        tokio::task::spawn_blocking(move || {
+            if let Ok(file) = std::fs::File::options().write(true).open(&path) {
+                file.set_modified(SystemTime::now()).ok();
+            }
+        })
+        .await
+        .ok();
+        Some(wav)
+    }
+
+    /// store wav and remove least recently used files if total size exceeds limit.
+    pub async fn put(&self, key: &str, wav: &[u8]) -> io::Result<()> {
+        // write into temporary file first so that readers never see partial wav.
+        let temporary = self.dir.join(format!("{}.tmp", key));
+        tokio::fs::write(&temporary, wav).await?;
+        tokio::fs::rename(&temporary, self.path(key)).await?;
+        self.index.lock().unwrap().touch(key, wav.len() as u64);
+        self.evict().await
+    }
+
+    /// total size of wav files in cache.
+    pub fn total_bytes(&self) -> u64 {
+        self.index.lock().unwrap().total
+    }
+
+    async fn evict(&self) -> io::Result<()> {
+        let evicted = self.index.lock().unwrap().evict(self.max_bytes);
+        for key in evicted {
+            match tokio::fs::remove_file(self.path(&key)).await {
+                // removed by other instance meanwhile.
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// synthesis request whose wav can be stored in [DiskCache].
+pub trait CacheKey {
+    /// key of [DiskCache] for the request. see [DiskCache::key].
+    fn cache_key(&self, engine_version: &str) -> String;
+}
+
+impl CacheKey for Synthesis {
+    fn cache_key(&self, engine_version: &str) -> String {
+        DiskCache::key(
+            engine_version,
+            &self.core_version,
+            self.speaker,
+            self.enable_interrogative_upspeak,
+            None,
+            &self.audio_query,
+        )
+    }
+}
+
+/// shares key with [Synthesis] with upspeak, as the engine always applies it.
+impl CacheKey for CancellableSynthesis {
+    fn cache_key(&self, engine_version: &str) -> String {
+        DiskCache::key(
+            engine_version,
+            &self.core_version,
+            self.speaker,
+            Some(true),
+            None,
+            &self.audio_query,
+        )
+    }
+}
+
+impl CacheKey for SynthesisMorphing {
+    fn cache_key(&self, engine_version: &str) -> String {
+        DiskCache::key(
+            engine_version,
+            &self.core_version,
+            self.base_speaker,
+            None,
+            Some((self.target_speaker, self.morph_rate)),
+            &self.audio_query,
+        )
+    }
+}
+
+/// synthesis which reads from and writes into [DiskCache].
+pub struct CachedSynthesis<T> {
+    pub cache: Arc<DiskCache>,
+    /// result of [crate::api::Version].
+    pub engine_version: String,
+    pub synthesis: T,
+}
+
+impl<T: CacheKey> CachedSynthesis<T> {
+    /// cached wav of the request.
+    pub async fn lookup(&self) -> Option<Vec<u8>> {
+        let key = self.synthesis.cache_key(&self.engine_version);
+        self.cache.get(&key).await
+    }
+
+    /// store wav of the request. used when it is synthesized elsewhere, e.g. by [crate::api::MultiSynthesis].
+    pub async fn store(&self, wav: &[u8]) {
+        let key = self.synthesis.cache_key(&self.engine_version);
+        if let Err(e) = self.cache.put(&key, wav).await {
+            log::warn!("failed to write synthesis cache {}", e);
+        }
+    }
+}
+
+#[async_trait]
+impl<T> Api for CachedSynthesis<T>
+where
+    T: Api<Response = Result<Vec<u8>, APIError>> + CacheKey + Send + Sync,
+{
+    type Response = Result<Vec<u8>, APIError>;
+
+    async fn call(&self) -> Self::Response {
+        if let Some(wav) = self.lookup().await {
+            return Ok(wav);
+        }
+        let wav = self.synthesis.call().await?;
+        self.store(&wav).await;
+        Ok(wav)
+    }
+}
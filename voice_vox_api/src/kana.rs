@@ -0,0 +1,218 @@
+//! AquesTalk like notation of accent phrases used by `is_kana` option of engine.
+//!
+//! ```text
+//! コ'レワ/テ_スト'、デ'スカ？
+//! ```
+//!
+//! * `'` : accent after mora.
+//! * `/` : boundary of accent phrases without pause.
+//! * `、` : boundary of accent phrases with pause.
+//! * `_` : next mora is devoiced.
+//! * `？` : accent phrase is interrogative. only at end of phrase.
+
+use crate::api_schema::{AccentPhrase, AccentPhraseInProject};
+
+const ACCENT: char = '\'';
+const NO_PAUSE: char = '/';
+const PAUSE: char = '、';
+const UNVOICE: char = '_';
+const INTERROGATION: char = '？';
+
+/// small kana which forms a mora with preceding kana.
+const SMALL: [char; 9] = ['ァ', 'ィ', 'ゥ', 'ェ', 'ォ', 'ャ', 'ュ', 'ョ', 'ヮ'];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KanaMora {
+    pub text: String,
+    pub unvoiced: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KanaPhrase {
+    pub moras: Vec<KanaMora>,
+    /// 1 origin position of accent.
+    pub accent: usize,
+    /// pause follows this phrase.
+    pub pause: bool,
+    pub interrogative: bool,
+}
+
+impl From<&AccentPhraseInProject> for KanaPhrase {
+    fn from(ap: &AccentPhraseInProject) -> Self {
+        Self {
+            moras: ap
+                .moras
+                .iter()
+                .map(|mora| KanaMora {
+                    text: mora.text.clone(),
+                    unvoiced: is_unvoiced(&mora.vowel),
+                })
+                .collect(),
+            accent: ap.accent.max(0) as usize,
+            pause: ap.pause_mora.is_some(),
+            interrogative: ap.isInterrogative.unwrap_or(false),
+        }
+    }
+}
+
+impl From<&AccentPhrase> for KanaPhrase {
+    fn from(ap: &AccentPhrase) -> Self {
+        Self {
+            moras: ap
+                .moras
+                .iter()
+                .map(|mora| KanaMora {
+                    text: mora.text.clone(),
+                    unvoiced: is_unvoiced(&mora.vowel),
+                })
+                .collect(),
+            accent: ap.accent.max(0) as usize,
+            pause: ap.pause_mora.is_some(),
+            interrogative: ap.is_interrogative.unwrap_or(false),
+        }
+    }
+}
+
+/// engine writes devoiced vowel in uppercase.
+fn is_unvoiced(vowel: &str) -> bool {
+    matches!(vowel, "A" | "I" | "U" | "E" | "O")
+}
+
+/// why kana could not be parsed. same as errors of engine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KanaError {
+    /// accent phrase containing text which is not katakana.
+    UnknownText(String),
+    /// accent before first mora.
+    AccentTop(String),
+    AccentTwice(String),
+    AccentNotFound(String),
+    /// 1 origin position of empty accent phrase.
+    EmptyPhrase(usize),
+    InterrogationMarkNotAtEnd(String),
+}
+
+impl std::fmt::Display for KanaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KanaError::UnknownText(text) => write!(f, "判別できない読み仮名があります: {}", text),
+            KanaError::AccentTop(text) => write!(f, "句頭にアクセントは置けません: {}", text),
+            KanaError::AccentTwice(text) => write!(
+                f,
+                "1つのアクセント句に二つ以上のアクセントは置けません: {}",
+                text
+            ),
+            KanaError::AccentNotFound(text) => write!(
+                f,
+                "アクセントを指定していないアクセント句があります: {}",
+                text
+            ),
+            KanaError::EmptyPhrase(position) => {
+                write!(f, "{}番目のアクセント句が空白です", position)
+            }
+            KanaError::InterrogationMarkNotAtEnd(text) => {
+                write!(f, "アクセント句末以外に「？」は置けません: {}", text)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KanaError {}
+
+/// serialize accent phrases into kana.
+pub fn to_kana(phrases: &[KanaPhrase]) -> String {
+    let mut kana = String::new();
+    for (i, phrase) in phrases.iter().enumerate() {
+        for (j, mora) in phrase.moras.iter().enumerate() {
+            if mora.unvoiced {
+                kana.push(UNVOICE);
+            }
+            kana.push_str(&mora.text);
+            if j + 1 == phrase.accent {
+                kana.push(ACCENT);
+            }
+        }
+        if phrase.interrogative {
+            kana.push(INTERROGATION);
+        }
+        if i + 1 < phrases.len() {
+            kana.push(if phrase.pause { PAUSE } else { NO_PAUSE });
+        }
+    }
+    kana
+}
+
+fn is_katakana(c: char) -> bool {
+    ('ァ'..='ヴ').contains(&c)
+}
+
+fn parse_phrase(text: &str, pause: bool) -> Result<KanaPhrase, KanaError> {
+    let mut moras: Vec<KanaMora> = Vec::new();
+    let mut accent = None;
+    let mut interrogative = false;
+    let mut unvoiced = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ACCENT if moras.is_empty() => return Err(KanaError::AccentTop(text.to_owned())),
+            ACCENT if accent.is_some() => return Err(KanaError::AccentTwice(text.to_owned())),
+            ACCENT => accent = Some(moras.len()),
+            INTERROGATION if chars.peek().is_some() => {
+                return Err(KanaError::InterrogationMarkNotAtEnd(text.to_owned()))
+            }
+            INTERROGATION => interrogative = true,
+            UNVOICE if !unvoiced => unvoiced = true,
+            c if is_katakana(c) && !SMALL.contains(&c) => {
+                let mut mora = c.to_string();
+                if let Some(small) = chars.next_if(|c| SMALL.contains(c)) {
+                    mora.push(small);
+                }
+                moras.push(KanaMora {
+                    text: mora,
+                    unvoiced,
+                });
+                unvoiced = false;
+            }
+            _ => return Err(KanaError::UnknownText(text.to_owned())),
+        }
+    }
+    // `_` must be followed by mora.
+    if unvoiced {
+        return Err(KanaError::UnknownText(text.to_owned()));
+    }
+    Ok(KanaPhrase {
+        moras,
+        accent: accent.ok_or_else(|| KanaError::AccentNotFound(text.to_owned()))?,
+        pause,
+        interrogative,
+    })
+}
+
+/// parse kana into accent phrases.
+///
+/// consonants, vowels, lengths and pitches are left to engine.
+pub fn parse_kana(kana: &str) -> Result<Vec<KanaPhrase>, KanaError> {
+    let mut phrases = Vec::new();
+    let mut rest = kana;
+    loop {
+        let (text, pause, next) = match rest.find([NO_PAUSE, PAUSE]) {
+            Some(at) => {
+                let delimiter = rest[at..].chars().next().unwrap();
+                (
+                    &rest[..at],
+                    delimiter == PAUSE,
+                    Some(&rest[at + delimiter.len_utf8()..]),
+                )
+            }
+            None => (rest, false, None),
+        };
+        if text.is_empty() {
+            return Err(KanaError::EmptyPhrase(phrases.len() + 1));
+        }
+        phrases.push(parse_phrase(text, pause)?);
+        match next {
+            Some(next) => rest = next,
+            None => return Ok(phrases),
+        }
+    }
+}
@@ -4,12 +4,198 @@
 
 pub mod api;
 pub mod api_schema;
+pub mod cache;
+pub mod kana;
 #[cfg(test)]
 mod test {
     use crate::api::{
-        Api, AudioQuery, ConnectWaves, CoreVersions, MultiSynthesis, Presets, SpeakerInfo,
-        Speakers, SupportedDevices, SynthesisMorphing, Version,
+        unzip_waves, APIError, Api, AudioQuery, CancellableSynthesis, ConnectWaves, CoreVersions,
+        MultiSynthesis, Presets, SpeakerInfo, Speakers, SupportedDevices, Synthesis,
+        SynthesisMorphing, Version,
     };
+    use crate::cache::{CacheKey, CachedSynthesis, DiskCache};
+    use crate::kana::{parse_kana, to_kana, KanaError};
+    use std::io::Write;
+    use std::sync::Arc;
+
+    /// directory unique to each run so that concurrent test runs do not share it.
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("{}_{}_{}", name, std::process::id(), nanos))
+    }
+
+    #[tokio::test]
+    async fn disk_cache_evicts_oldest() {
+        let dir = temp_dir("voice_vox_api_disk_cache_test");
+        let cache = DiskCache::new(dir.clone(), 8).await.unwrap();
+        cache.put("a", b"1234").await.unwrap();
+        cache.put("b", b"5678").await.unwrap();
+        assert_eq!(cache.get("a").await.unwrap(), b"1234");
+        // overwriting same key is not counted twice.
+        cache.put("a", b"1234").await.unwrap();
+        assert_eq!(cache.total_bytes(), 8);
+        cache.put("c", b"90").await.unwrap();
+        assert!(cache.get("b").await.is_none());
+        assert!(cache.get("a").await.is_some());
+        assert!(cache.get("c").await.is_some());
+        assert_eq!(cache.total_bytes(), 6);
+        drop(cache);
+
+        // access order is restored from modified time on open.
+        let now = std::time::SystemTime::now();
+        for (key, age) in [("a", 10), ("c", 20)] {
+            std::fs::File::options()
+                .write(true)
+                .open(dir.join(format!("{}.wav", key)))
+                .unwrap()
+                .set_modified(now - std::time::Duration::from_secs(age))
+                .unwrap();
+        }
+        let reopened = DiskCache::new(dir.clone(), 8).await.unwrap();
+        assert_eq!(reopened.total_bytes(), 6);
+        reopened.put("d", b"567").await.unwrap();
+        assert!(reopened.get("c").await.is_none());
+        assert!(reopened.get("a").await.is_some());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    fn silent_query() -> crate::api_schema::AudioQuery {
+        crate::api_schema::AudioQuery {
+            accent_phrases: vec![],
+            speedScale: 1.0,
+            pitchScale: 0.0,
+            intonationScale: 1.0,
+            volumeScale: 1.0,
+            prePhonemeLength: 0.1,
+            postPhonemeLength: 0.1,
+            outputSamplingRate: 24000,
+            outputStereo: false,
+            kana: None,
+        }
+    }
+
+    #[test]
+    fn disk_cache_key_of_morphing() {
+        let morphing = |target_speaker, morph_rate| SynthesisMorphing {
+            base_speaker: 2,
+            target_speaker,
+            morph_rate,
+            core_version: None,
+            audio_query: silent_query(),
+        };
+        let synthesis = |enable_interrogative_upspeak| Synthesis {
+            speaker: 2,
+            enable_interrogative_upspeak,
+            core_version: None,
+            audio_query: silent_query(),
+        };
+        let cancellable = CancellableSynthesis {
+            speaker: 2,
+            core_version: None,
+            audio_query: silent_query(),
+        };
+        let key = |request: &dyn CacheKey| request.cache_key("0.11.4");
+        assert_ne!(key(&synthesis(None)), key(&morphing(3, 0.5)));
+        assert_ne!(key(&morphing(3, 0.5)), key(&morphing(3, 0.6)));
+        assert_ne!(key(&morphing(3, 0.5)), key(&morphing(4, 0.5)));
+        assert_eq!(key(&morphing(3, 0.5)), key(&morphing(3, 0.5)));
+        assert_eq!(key(&cancellable), key(&synthesis(Some(true))));
+        assert_ne!(key(&cancellable), key(&synthesis(Some(false))));
+    }
+
+    #[tokio::test]
+    async fn cached_synthesis_reads_stored_wav() {
+        let dir = temp_dir("voice_vox_api_cached_synthesis_test");
+        let cache = Arc::new(DiskCache::new(dir.clone(), 1024).await.unwrap());
+        let cached = |speaker| CachedSynthesis {
+            cache: cache.clone(),
+            engine_version: "0.11.4".to_owned(),
+            synthesis: Synthesis {
+                speaker,
+                enable_interrogative_upspeak: Some(true),
+                core_version: None,
+                audio_query: silent_query(),
+            },
+        };
+        assert!(cached(2).lookup().await.is_none());
+        cached(2).store(b"wav").await;
+        assert_eq!(cached(2).lookup().await.unwrap(), b"wav");
+        // served without engine.
+        assert_eq!(cached(2).call().await.unwrap(), b"wav");
+        assert!(cached(3).lookup().await.is_none());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn kana_round_trip() {
+        for kana in [
+            "コ'レワ/テ_スト'、デ'スカ？",
+            "キョ'オ",
+            "_シ'タ/ヴァ'イオリン？、ン'",
+        ] {
+            let phrases = parse_kana(kana).unwrap();
+            assert_eq!(to_kana(&phrases), kana);
+        }
+        let phrases = parse_kana("テ_スト'、デ'スカ？").unwrap();
+        assert_eq!(phrases.len(), 2);
+        assert_eq!(phrases[0].accent, 3);
+        assert!(phrases[0].moras[1].unvoiced);
+        assert!(phrases[0].pause);
+        assert!(phrases[1].interrogative);
+        assert_eq!(phrases[1].moras[1].text, "ス");
+    }
+
+    #[test]
+    fn kana_errors() {
+        assert_eq!(
+            parse_kana("'ア"),
+            Err(KanaError::AccentTop("'ア".to_owned()))
+        );
+        assert_eq!(
+            parse_kana("ア'イ'"),
+            Err(KanaError::AccentTwice("ア'イ'".to_owned()))
+        );
+        assert_eq!(
+            parse_kana("ア'/イ"),
+            Err(KanaError::AccentNotFound("イ".to_owned()))
+        );
+        assert_eq!(parse_kana("ア'//イ'"), Err(KanaError::EmptyPhrase(2)));
+        assert_eq!(
+            parse_kana("ア？'"),
+            Err(KanaError::InterrogationMarkNotAtEnd("ア？'".to_owned()))
+        );
+        assert_eq!(
+            parse_kana("あ'"),
+            Err(KanaError::UnknownText("あ'".to_owned()))
+        );
+        assert_eq!(
+            parse_kana("ア_'"),
+            Err(KanaError::UnknownText("ア_'".to_owned()))
+        );
+    }
+
+    #[test]
+    fn unzip_waves_in_order() {
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        let mut writer = zip::ZipWriter::new(&mut buffer);
+        for (name, body) in [("001.wav", b"first"), ("002.wav", b"secnd")] {
+            writer
+                .start_file(name, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(body).unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+        let waves = unzip_waves(buffer.get_ref()).unwrap();
+        assert_eq!(waves, vec![b"first".to_vec(), b"secnd".to_vec()]);
+        assert!(matches!(
+            unzip_waves(b"not a zip"),
+            Err(APIError::MalformedArchive(_))
+        ));
+    }
 
     #[tokio::test]
     async fn call_multi_synthesis() {
@@ -29,7 +215,7 @@ mod test {
         .call()
         .await
         .unwrap();
-        MultiSynthesis {
+        let waves = MultiSynthesis {
             speaker: 0,
             core_version: None,
             audio_query: vec![aq0, aq1],
@@ -37,6 +223,7 @@ mod test {
         .call()
         .await
         .unwrap();
+        assert_eq!(waves.len(), 2);
     }
 
     #[tokio::test]